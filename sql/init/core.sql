@@ -19,6 +19,10 @@ CREATE TABLE IF NOT EXISTS FileNode (
     is_deployed BOOLEAN DEFAULT FALSE NOT NULL,                   -- 文件部署状态  与文件夹无关
     size uint64,                                                  -- 文件大小 文件夹为NULL
     hash VARCHAR,                                                 -- 文件哈希 文件夹为NULL
+    mtime BIGINT,                                                 -- 修改时间（毫秒时间戳）
+    dev UBIGINT,                                                  -- 设备号 仅Unix
+    inode UBIGINT,                                                -- inode 仅Unix
+    mode UINTEGER,                                                -- 权限模式 仅Unix
 
     full_path VARCHAR GENERATED ALWAYS AS (array_to_string(path_segments, '/')) VIRTUAL,
     depth INT GENERATED ALWAYS AS (array_length(path_segments)) VIRTUAL,
@@ -0,0 +1,36 @@
+-- 部署记录表
+-- 记录由本库写入部署目标目录的每个文件，撤销部署时据此安全删除
+CREATE TABLE IF NOT EXISTS DeployedFile (
+    target_root VARCHAR NOT NULL,                                 -- 部署目标根目录
+    path VARCHAR NOT NULL,                                        -- 相对路径
+    file_id UUID NOT NULL,                                        -- 来源文件id
+    pack_id UUID NOT NULL,                                        -- 来源包id
+    source_path VARCHAR NOT NULL,                                 -- 来源文件绝对路径
+    method VARCHAR NOT NULL,                                      -- 部署方式 symlink / hardlink / copy
+    hash VARCHAR,                                                 -- 部署时的文件哈希
+    deploy_time TIMESTAMP DEFAULT CURRENT_TIMESTAMP,              -- 部署时间
+    PRIMARY KEY (target_root, path)
+);
+
+-- 部署时新建的目录，撤销部署时仅在目录为空时删除
+CREATE TABLE IF NOT EXISTS DeployedDir (
+    target_root VARCHAR NOT NULL,                                 -- 部署目标根目录
+    path VARCHAR NOT NULL,                                        -- 相对路径
+    PRIMARY KEY (target_root, path)
+);
+
+-- 部署前目标目录中已存在的原始文件，撤销部署时放回原位
+CREATE TABLE IF NOT EXISTS DeployBackup (
+    target_root VARCHAR NOT NULL,                                 -- 部署目标根目录
+    path VARCHAR NOT NULL,                                        -- 相对路径
+    backup_path VARCHAR NOT NULL,                                 -- 备份文件绝对路径
+    hash VARCHAR,                                                 -- 原始文件哈希
+    backup_time TIMESTAMP DEFAULT CURRENT_TIMESTAMP,              -- 备份时间
+    PRIMARY KEY (target_root, path)
+);
+
+-- 部署目标的会话起始时间，收集时只处理此后写入目标目录的文件
+CREATE TABLE IF NOT EXISTS DeployTarget (
+    target_root VARCHAR PRIMARY KEY,                              -- 部署目标根目录
+    session_start BIGINT NOT NULL                                 -- 会话起始时间（毫秒时间戳）
+);
@@ -0,0 +1,6 @@
+-- 文件元数据
+-- 由旧版本创建的数据库中 FileNode 缺少以下列
+ALTER TABLE FileNode ADD COLUMN IF NOT EXISTS mtime BIGINT;
+ALTER TABLE FileNode ADD COLUMN IF NOT EXISTS dev UBIGINT;
+ALTER TABLE FileNode ADD COLUMN IF NOT EXISTS inode UBIGINT;
+ALTER TABLE FileNode ADD COLUMN IF NOT EXISTS mode UINTEGER;
@@ -0,0 +1,8 @@
+-- 全局忽略规则
+-- gitignore 风格，按 id 顺序生效，后添加的规则优先
+CREATE SEQUENCE IF NOT EXISTS ignore_rule_seq START 1 INCREMENT BY 1;
+CREATE TABLE IF NOT EXISTS IgnoreRule (
+    id INTEGER PRIMARY KEY DEFAULT nextval('ignore_rule_seq'),
+    pattern VARCHAR NOT NULL UNIQUE,                              -- 规则
+    add_time TIMESTAMP DEFAULT CURRENT_TIMESTAMP                  -- 添加时间
+);
@@ -0,0 +1,3 @@
+-- 压缩包来源的包
+-- archive_kind 为空时 base_path 为目录，否则 base_path 为压缩包文件
+ALTER TABLE FNPack ADD COLUMN IF NOT EXISTS archive_kind VARCHAR;
@@ -0,0 +1,15 @@
+-- 模式覆盖规则
+-- 按目录前缀或 glob 将匹配的路径交给指定包，规则顺序大的优先
+-- 覆盖树中的优先级：独立覆盖规则 > 模式覆盖规则 > 包优先级
+CREATE SEQUENCE IF NOT EXISTS pattern_rule_seq START 1 INCREMENT BY 1;
+CREATE SEQUENCE IF NOT EXISTS pattern_rule_order_seq START 1 INCREMENT BY 1;
+CREATE TABLE IF NOT EXISTS PriorityRule (
+    id INTEGER PRIMARY KEY DEFAULT nextval('pattern_rule_seq'),
+    kind VARCHAR NOT NULL,                                        -- 规则类型 prefix / glob
+    pattern VARCHAR NOT NULL,                                     -- 目录前缀或 glob
+    regex VARCHAR NOT NULL,                                       -- 由 pattern 生成、供视图匹配的正则
+    pack_id UUID NOT NULL,                                        -- 匹配的路径取自此包
+    rule_order DOUBLE NOT NULL DEFAULT nextval('pattern_rule_order_seq'), -- 规则顺序，大的优先
+    is_active BOOLEAN DEFAULT TRUE NOT NULL,                      -- 规则启用状态
+    add_time TIMESTAMP DEFAULT CURRENT_TIMESTAMP                  -- 添加时间
+);
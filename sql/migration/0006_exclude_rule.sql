@@ -0,0 +1,11 @@
+-- 排除规则
+-- 匹配的路径从覆盖树中移除，不论由哪些包提供
+CREATE SEQUENCE IF NOT EXISTS exclude_rule_seq START 1 INCREMENT BY 1;
+CREATE TABLE IF NOT EXISTS ExcludeRule (
+    id INTEGER PRIMARY KEY DEFAULT nextval('exclude_rule_seq'),
+    kind VARCHAR NOT NULL,                                        -- 规则类型 exact / prefix / glob
+    pattern VARCHAR NOT NULL,                                     -- 路径、目录前缀或 glob
+    regex VARCHAR NOT NULL,                                       -- 由 pattern 生成、供视图匹配的正则
+    is_active BOOLEAN DEFAULT TRUE NOT NULL,                      -- 规则启用状态
+    add_time TIMESTAMP DEFAULT CURRENT_TIMESTAMP                  -- 添加时间
+);
@@ -0,0 +1,96 @@
+-- 配置方案
+-- 每个方案拥有独立的包顺序、包启用状态、文件启用状态、独立覆盖规则、模式覆盖规则、排除规则与部署目标
+-- 视图与查询只读取当前方案
+CREATE SEQUENCE IF NOT EXISTS profile_seq START 1 INCREMENT BY 1;
+CREATE TABLE IF NOT EXISTS Profile (
+    id INTEGER PRIMARY KEY DEFAULT nextval('profile_seq'),
+    name VARCHAR NOT NULL UNIQUE,                                 -- 方案名称
+    target_root VARCHAR,                                          -- 部署目标根目录
+    is_current BOOLEAN DEFAULT FALSE NOT NULL,                    -- 是否为当前方案
+    add_time TIMESTAMP DEFAULT CURRENT_TIMESTAMP                  -- 添加时间
+);
+
+-- 原有的设置归入默认方案
+INSERT INTO Profile (name, is_current) VALUES ('default', TRUE);
+
+-- 包优先级表按方案记录
+CREATE TABLE PriorityPackOld AS SELECT * FROM PriorityPack;
+DROP TABLE PriorityPack;
+CREATE TABLE PriorityPack (
+    profile_id INTEGER NOT NULL,                                 -- 方案id
+    pack_id UUID NOT NULL,
+    priority DOUBLE NOT NULL DEFAULT nextval('priority_seq'),    -- 包覆盖优先级
+    is_deployed BOOLEAN DEFAULT FALSE NOT NULL,                  -- 包部署状态
+    is_active BOOLEAN DEFAULT FALSE NOT NULL,                    -- 包启用状态
+    PRIMARY KEY (profile_id, pack_id),
+    UNIQUE (profile_id, priority)
+);
+INSERT INTO PriorityPack (profile_id, pack_id, priority, is_deployed, is_active)
+SELECT (SELECT id FROM Profile WHERE is_current), pack_id, priority, is_deployed, is_active
+FROM PriorityPackOld;
+DROP TABLE PriorityPackOld;
+
+-- 独立覆盖规则按方案记录
+CREATE TABLE PriorityFNOld AS SELECT * FROM PriorityFN;
+DROP TABLE PriorityFN;
+CREATE TABLE PriorityFN (
+    profile_id INTEGER NOT NULL,                                  -- 方案id
+    path VARCHAR NOT NULL,                                        -- 覆盖路径
+    main_id UUID NOT NULL,                                        -- 主要文件id
+    pack_id UUID NOT NULL,                                        -- 所属包id
+    is_active BOOLEAN DEFAULT TRUE NOT NULL,                      -- 覆盖规则启用状态
+    PRIMARY KEY (profile_id, path)
+);
+INSERT INTO PriorityFN (profile_id, path, main_id, pack_id, is_active)
+SELECT (SELECT id FROM Profile WHERE is_current), path, main_id, pack_id, is_active
+FROM PriorityFNOld;
+DROP TABLE PriorityFNOld;
+
+-- 模式覆盖规则按方案记录
+CREATE TABLE PriorityRuleOld AS SELECT * FROM PriorityRule;
+DROP TABLE PriorityRule;
+CREATE TABLE PriorityRule (
+    id INTEGER PRIMARY KEY DEFAULT nextval('pattern_rule_seq'),
+    profile_id INTEGER NOT NULL,                                  -- 方案id
+    kind VARCHAR NOT NULL,                                        -- 规则类型 prefix / glob
+    pattern VARCHAR NOT NULL,                                     -- 目录前缀或 glob
+    regex VARCHAR NOT NULL,                                       -- 由 pattern 生成、供视图匹配的正则
+    pack_id UUID NOT NULL,                                        -- 匹配的路径取自此包
+    rule_order DOUBLE NOT NULL DEFAULT nextval('pattern_rule_order_seq'), -- 规则顺序，大的优先
+    is_active BOOLEAN DEFAULT TRUE NOT NULL,                      -- 规则启用状态
+    add_time TIMESTAMP DEFAULT CURRENT_TIMESTAMP                  -- 添加时间
+);
+INSERT INTO PriorityRule (id, profile_id, kind, pattern, regex, pack_id, rule_order, is_active, add_time)
+SELECT id, (SELECT id FROM Profile WHERE is_current), kind, pattern, regex, pack_id, rule_order, is_active, add_time
+FROM PriorityRuleOld;
+DROP TABLE PriorityRuleOld;
+
+-- 排除规则按方案记录
+CREATE TABLE ExcludeRuleOld AS SELECT * FROM ExcludeRule;
+DROP TABLE ExcludeRule;
+CREATE TABLE ExcludeRule (
+    id INTEGER PRIMARY KEY DEFAULT nextval('exclude_rule_seq'),
+    profile_id INTEGER NOT NULL,                                  -- 方案id
+    kind VARCHAR NOT NULL,                                        -- 规则类型 exact / prefix / glob
+    pattern VARCHAR NOT NULL,                                     -- 路径、目录前缀或 glob
+    regex VARCHAR NOT NULL,                                       -- 由 pattern 生成、供视图匹配的正则
+    is_active BOOLEAN DEFAULT TRUE NOT NULL,                      -- 规则启用状态
+    add_time TIMESTAMP DEFAULT CURRENT_TIMESTAMP                  -- 添加时间
+);
+INSERT INTO ExcludeRule (id, profile_id, kind, pattern, regex, is_active, add_time)
+SELECT id, (SELECT id FROM Profile WHERE is_current), kind, pattern, regex, is_active, add_time
+FROM ExcludeRuleOld;
+DROP TABLE ExcludeRuleOld;
+
+-- 文件启用状态按方案记录，没有记录的文件视为启用
+-- FileNode.is_active 不再使用
+CREATE TABLE IF NOT EXISTS ProfileFile (
+    profile_id INTEGER NOT NULL,                                  -- 方案id
+    file_id UUID NOT NULL,                                        -- 文件id
+    is_active BOOLEAN NOT NULL,                                   -- 文件启用状态
+    PRIMARY KEY (profile_id, file_id)
+);
+INSERT INTO ProfileFile (profile_id, file_id, is_active)
+SELECT (SELECT id FROM Profile WHERE is_current), id, FALSE
+FROM FileNode
+WHERE NOT is_active;
@@ -0,0 +1,13 @@
+-- 包关系
+-- requires：依赖目标包，可附带版本要求，目标包应排在其前
+-- incompatible：不能与目标包同时启用
+-- load_after：目标包启用时应排在其前
+-- load_before：目标包启用时应排在其后
+CREATE TABLE IF NOT EXISTS PackRelation (
+    pack_id UUID NOT NULL,                                        -- 声明关系的包id
+    kind VARCHAR NOT NULL,                                        -- 关系类型 requires / incompatible / load_after / load_before
+    target_id UUID NOT NULL,                                      -- 目标包id，可以尚未入库
+    version_req VARCHAR,                                          -- 版本要求，如 >=1.2，仅 requires
+    add_time TIMESTAMP DEFAULT CURRENT_TIMESTAMP,                 -- 添加时间
+    PRIMARY KEY (pack_id, kind, target_id)
+);
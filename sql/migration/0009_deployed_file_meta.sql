@@ -0,0 +1,4 @@
+-- 部署记录的文件元数据
+-- 复制与硬链接部署的文件大小与修改时间都未变化时不再重新计算哈希
+ALTER TABLE DeployedFile ADD COLUMN IF NOT EXISTS size UBIGINT;
+ALTER TABLE DeployedFile ADD COLUMN IF NOT EXISTS mtime BIGINT;
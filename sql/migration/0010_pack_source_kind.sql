@@ -0,0 +1,6 @@
+-- 包来源类型
+-- dir：base_path 为目录；archive：base_path 为压缩包文件，格式见 archive_kind；
+-- custom：内存等自定义来源，不能从数据库中恢复，重新扫描、校验与部署前需要重新提供来源
+-- 旧版本没有记录来源类型，自定义来源的包无法区分，按 archive_kind 归为目录或压缩包
+ALTER TABLE FNPack ADD COLUMN IF NOT EXISTS source_kind VARCHAR DEFAULT 'dir';
+UPDATE FNPack SET source_kind = CASE WHEN archive_kind IS NULL THEN 'dir' ELSE 'archive' END;
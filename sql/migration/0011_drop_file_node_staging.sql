@@ -0,0 +1,3 @@
+-- 文件节点批量写入的中转表改为每个连接各自的临时表
+-- 持久的中转表在多个连接间共享，可能导入或删除其他连接写入的数据
+DROP TABLE IF EXISTS main.FileNodeStaging;
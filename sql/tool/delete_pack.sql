@@ -3,16 +3,39 @@ DELETE FROM InfoBase
 WHERE
     id = ?;
 
+-- 以下设置在所有方案中删除
 -- 删除独立覆盖规则
 DELETE FROM PriorityFN
 WHERE
     pack_id = ?;
 
+-- 删除包声明的关系
+DELETE FROM PackRelation
+WHERE
+    pack_id = ?;
+
+-- 删除模式覆盖规则
+DELETE FROM PriorityRule
+WHERE
+    pack_id = ?;
+
 -- 删除包的优先级设置
 DELETE FROM PriorityPack
 WHERE
     pack_id = ?;
 
+-- 删除文件的启用状态
+DELETE FROM ProfileFile
+WHERE
+    file_id IN (
+        SELECT
+            id
+        FROM
+            FileNode
+        WHERE
+            pack_id = ?
+    );
+
 -- 删除文件节点
 DELETE FROM FileNode
 WHERE
@@ -1,15 +1,17 @@
 -- 查询部署路径和绝对路径
 SELECT
-    top_file_id,
-    top_pack_id,
-    top_parent_id,
-    array_to_string (path_segments, '/') AS relative_path,
-    top_base_path || '/' || array_to_string (path_segments, '/') AS full_file_path,
-    top_is_deployed
+    d.top_file_id,
+    d.top_pack_id,
+    d.top_parent_id,
+    array_to_string (d.path_segments, '/') AS relative_path,
+    d.top_base_path || '/' || array_to_string (d.path_segments, '/') AS full_file_path,
+    d.top_is_deployed,
+    fn.hash
 FROM
-    deployed_files
+    deployed_files d
+    LEFT JOIN FileNode fn ON fn.id = d.top_file_id
 WHERE
-    top_file_active
-    AND top_is_dir = false
+    d.top_file_active
+    AND d.top_is_dir = false
 ORDER BY
     full_file_path;
\ No newline at end of file
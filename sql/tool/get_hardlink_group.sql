@@ -0,0 +1,26 @@
+WITH shared_inodes AS (
+  SELECT dev, inode
+  FROM FileNode
+  WHERE is_dir = 0 
+    AND inode IS NOT NULL
+  GROUP BY dev, inode
+  HAVING COUNT(*) > 1  -- 只选择被多个节点引用的 inode
+),
+hardlink_groups AS (
+  SELECT 
+    fn.dev,
+    fn.inode,
+        LIST({
+            'id': fn.id,
+            'pack_id': fn.pack_id,
+            'pack_path': (SELECT base_path FROM FNPack WHERE id = fn.pack_id),
+            'path': fn.full_path,
+            'hash': fn.hash
+        }) AS link_group
+  FROM FileNode fn
+  JOIN shared_inodes s ON fn.dev = s.dev AND fn.inode = s.inode
+  WHERE fn.is_dir = 0  -- 只处理文件
+  GROUP BY fn.dev, fn.inode  -- 按设备号与 inode 分组
+)
+SELECT link_group
+FROM hardlink_groups;
\ No newline at end of file
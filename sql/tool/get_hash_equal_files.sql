@@ -1,13 +1,13 @@
 WITH target_file AS (
   SELECT hash, pack_id 
   FROM FileNode 
-  WHERE id = '$' AND hash IS NOT NULL
+  WHERE id = ? AND hash IS NOT NULL
 ),
 duplicate_hashes AS (
   SELECT hash
   FROM FileNode 
   WHERE hash = (SELECT hash FROM target_file)
-    AND id <> '$'
+    AND id <> ?
     AND is_dir = false
 )
 SELECT 
@@ -1,12 +1,14 @@
 
--- 重新编号为连续整数（保持顺序）
+-- 各方案分别重新编号为连续整数（保持顺序）
 UPDATE PriorityPack 
 SET priority = ranked.new_priority
 FROM (
     SELECT 
+        profile_id,
         pack_id, 
-        ROW_NUMBER() OVER (ORDER BY priority) AS new_priority
+        ROW_NUMBER() OVER (PARTITION BY profile_id ORDER BY priority) AS new_priority
     FROM PriorityPack
 ) ranked
-WHERE PriorityPack.pack_id = ranked.pack_id;
+WHERE PriorityPack.profile_id = ranked.profile_id
+    AND PriorityPack.pack_id = ranked.pack_id;
 
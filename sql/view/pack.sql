@@ -1,4 +1,4 @@
--- 主要pack信息视图
+-- 主要pack信息视图，优先级与状态取自当前方案
 CREATE
 OR REPLACE VIEW view_pack_info AS
 SELECT
@@ -19,4 +19,12 @@ SELECT
 FROM
     FNPack p
     LEFT JOIN PriorityPack pr ON p.id = pr.pack_id
+    AND pr.profile_id = (
+        SELECT
+            id
+        FROM
+            Profile
+        WHERE
+            is_current
+    )
     LEFT JOIN InfoBase b ON p.id = b.id
\ No newline at end of file
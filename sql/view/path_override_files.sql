@@ -1,4 +1,7 @@
 -- filenode覆盖视图，仅启用
+-- 停用的文件不参与覆盖，由下一个包中的同路径文件接管；只有停用的文件时该路径的顶层文件为停用状态
+-- 匹配排除规则的路径不出现在视图中
+-- 包优先级与启用状态、文件启用状态、独立覆盖规则、模式覆盖规则与排除规则取自当前方案
 CREATE OR REPLACE VIEW deployed_files AS
 WITH ranked_files AS (
     SELECT
@@ -7,7 +10,7 @@ WITH ranked_files AS (
         fn.id            AS file_id,
         fn.parent_id     AS parent_id,
         pp.priority,
-        fn.is_active     AS file_active,
+        COALESCE(pf.is_active, TRUE) AS file_active,
         COALESCE(pp.is_active, FALSE) AS pack_active,
         fp.base_path,
         fn.depth,
@@ -15,10 +18,13 @@ WITH ranked_files AS (
         fn.is_deployed,
         ROW_NUMBER() OVER (
             PARTITION BY fn.path_segments
-            ORDER BY pp.priority DESC
+            ORDER BY COALESCE(pf.is_active, TRUE) DESC, pp.priority DESC
         ) AS rn
     FROM FileNode fn
     LEFT JOIN PriorityPack pp ON fn.pack_id = pp.pack_id
+        AND pp.profile_id = (SELECT id FROM Profile WHERE is_current)
+    LEFT JOIN ProfileFile pf ON fn.id = pf.file_id
+        AND pf.profile_id = (SELECT id FROM Profile WHERE is_current)
     LEFT JOIN FNPack fp ON fn.pack_id = fp.id
     WHERE pp.is_active = true
 ),
@@ -59,7 +65,7 @@ priority_fn_files AS (
         fn.id                         AS top_file_id,
         fn.parent_id                  AS top_parent_id,
         pp.priority                   AS top_priority,
-        fn.is_active                  AS top_file_active,
+        COALESCE(pf.is_active, TRUE)  AS top_file_active,
         COALESCE(pp.is_active, TRUE)  AS top_pack_active,
         fp.base_path                  AS top_base_path,
         fn.is_dir                     AS top_is_dir,
@@ -67,22 +73,64 @@ priority_fn_files AS (
     FROM PriorityFN pfn
     JOIN FileNode fn       ON pfn.main_id = fn.id
     LEFT JOIN PriorityPack pp ON fn.pack_id = pp.pack_id
+        AND pp.profile_id = pfn.profile_id
+    LEFT JOIN ProfileFile pf ON fn.id = pf.file_id
+        AND pf.profile_id = pfn.profile_id
     LEFT JOIN FNPack fp ON fn.pack_id = fp.id
     WHERE pfn.is_active = TRUE
+      AND pfn.profile_id = (SELECT id FROM Profile WHERE is_current)
+      AND COALESCE(pf.is_active, TRUE)
+),
+pattern_rule_files AS (
+    SELECT
+        fn.path_segments,
+        fn.pack_id                    AS top_pack_id,
+        fn.id                         AS top_file_id,
+        fn.parent_id                  AS top_parent_id,
+        pp.priority                   AS top_priority,
+        COALESCE(pf.is_active, TRUE)  AS top_file_active,
+        pp.is_active                  AS top_pack_active,
+        fp.base_path                  AS top_base_path,
+        fn.is_dir                     AS top_is_dir,
+        fn.is_deployed                AS top_is_deployed,
+        ROW_NUMBER() OVER (
+            PARTITION BY fn.path_segments
+            ORDER BY pr.rule_order DESC, pr.id DESC
+        ) AS rn
+    FROM PriorityRule pr
+    JOIN FileNode fn       ON pr.pack_id = fn.pack_id
+    JOIN PriorityPack pp   ON fn.pack_id = pp.pack_id
+        AND pp.profile_id = pr.profile_id
+    LEFT JOIN ProfileFile pf ON fn.id = pf.file_id
+        AND pf.profile_id = pp.profile_id
+    LEFT JOIN FNPack fp ON fn.pack_id = fp.id
+    WHERE pr.is_active = TRUE
+      AND pr.profile_id = (SELECT id FROM Profile WHERE is_current)
+      AND pp.is_active = TRUE
+      AND COALESCE(pf.is_active, TRUE)
+      AND regexp_full_match(array_to_string(fn.path_segments, '/'), pr.regex)
 )
 SELECT
     a.path_segments,
     a.depth,
     a.pairs,
-    COALESCE(pfn.top_pack_id,      tpf.top_pack_id)      AS top_pack_id,
-    COALESCE(pfn.top_file_id,      tpf.top_file_id)      AS top_file_id,
-    COALESCE(pfn.top_parent_id,    tpf.top_parent_id)    AS top_parent_id,
-    COALESCE(pfn.top_priority,     tpf.top_priority)     AS top_priority,
-    COALESCE(pfn.top_file_active,  tpf.top_file_active)  AS top_file_active,
-    COALESCE(pfn.top_pack_active,  tpf.top_pack_active)  AS top_pack_active,
-    COALESCE(pfn.top_base_path,    tpf.top_base_path)    AS top_base_path,
-    COALESCE(pfn.top_is_dir,       tpf.top_is_dir)       AS top_is_dir,
-    COALESCE(pfn.top_is_deployed,  tpf.top_is_deployed)  AS top_is_deployed,
+    COALESCE(pfn.top_pack_id,       prf.top_pack_id,       tpf.top_pack_id)       AS top_pack_id,
+    COALESCE(pfn.top_file_id,       prf.top_file_id,       tpf.top_file_id)       AS top_file_id,
+    COALESCE(pfn.top_parent_id,     prf.top_parent_id,     tpf.top_parent_id)     AS top_parent_id,
+    COALESCE(pfn.top_priority,      prf.top_priority,      tpf.top_priority)      AS top_priority,
+    COALESCE(pfn.top_file_active,   prf.top_file_active,   tpf.top_file_active)   AS top_file_active,
+    COALESCE(pfn.top_pack_active,   prf.top_pack_active,   tpf.top_pack_active)   AS top_pack_active,
+    COALESCE(pfn.top_base_path,     prf.top_base_path,     tpf.top_base_path)     AS top_base_path,
+    COALESCE(pfn.top_is_dir,        prf.top_is_dir,        tpf.top_is_dir)        AS top_is_dir,
+    COALESCE(pfn.top_is_deployed,   prf.top_is_deployed,   tpf.top_is_deployed)   AS top_is_deployed,
 FROM aggregated_files   a
 LEFT JOIN top_priority_files tpf ON a.path_segments = tpf.path_segments
-LEFT JOIN priority_fn_files  pfn ON a.path_segments = pfn.path_segments;
\ No newline at end of file
+LEFT JOIN priority_fn_files  pfn ON a.path_segments = pfn.path_segments
+LEFT JOIN pattern_rule_files prf ON a.path_segments = prf.path_segments AND prf.rn = 1
+WHERE NOT EXISTS (
+    SELECT 1
+    FROM ExcludeRule er
+    WHERE er.is_active = TRUE
+      AND er.profile_id = (SELECT id FROM Profile WHERE is_current)
+      AND regexp_full_match(array_to_string(a.path_segments, '/'), er.regex)
+);
\ No newline at end of file
@@ -0,0 +1,368 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::File,
+    io::{Cursor, Read},
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+};
+
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, IGNORE_FILE, IgnoreRules, PackSource, Result, ScanState, SourceEntry, SourceKind,
+    model_insert::hash_reader,
+};
+
+/// 压缩包格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveKind {
+    /// .zip
+    Zip,
+    /// .tar
+    Tar,
+    /// .tar.gz / .tgz
+    TarGz,
+}
+
+impl ArchiveKind {
+    /// 数据库中保存的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::Tar => "tar",
+            Self::TarGz => "tar.gz",
+        }
+    }
+
+    /// 从数据库中保存的名称解析
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "zip" => Some(Self::Zip),
+            "tar" => Some(Self::Tar),
+            "tar.gz" => Some(Self::TarGz),
+            _ => None,
+        }
+    }
+
+    /// 根据文件扩展名判断格式，不区分大小写
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// 流式读取时单个条目的元数据
+struct EntryMeta {
+    is_dir: bool,
+    size: u64,
+    mtime: Option<i64>,
+}
+
+/// 压缩包，条目按顺序流式读取，不需要解压到磁盘
+#[derive(Debug, Clone)]
+pub struct Archive {
+    path: String,
+    kind: ArchiveKind,
+}
+
+impl Archive {
+    /// 创建指定格式的压缩包
+    pub fn new(path: &str, kind: ArchiveKind) -> Self {
+        Self {
+            path: path.to_string(),
+            kind,
+        }
+    }
+
+    /// 打开压缩包，格式由扩展名判断
+    pub fn open(path: &str) -> Result<Self> {
+        if !Path::new(path).is_file() {
+            return Err(Error::NotFound(format!("压缩包: {}", path)));
+        }
+        let kind = ArchiveKind::from_path(Path::new(path))
+            .ok_or_else(|| Error::InvalidInput(format!("不支持的压缩包格式: {}", path)))?;
+        Ok(Self::new(path, kind))
+    }
+
+    /// 压缩包路径
+    pub fn path(&self) -> &Path {
+        Path::new(&self.path)
+    }
+
+    /// 压缩包格式
+    pub fn kind(&self) -> ArchiveKind {
+        self.kind
+    }
+
+    /// 读取单个文件的全部内容，不存在时返回 None
+    pub fn read_file(&self, segments: &[String]) -> Result<Option<Vec<u8>>> {
+        let mut found = None;
+        self.visit(|entry_segments, meta, reader| {
+            if meta.is_dir || entry_segments != segments {
+                return Ok(ControlFlow::Continue(()));
+            }
+            let mut data = Vec::with_capacity(meta.size as usize);
+            reader.read_to_end(&mut data)?;
+            found = Some(data);
+            Ok(ControlFlow::Break(()))
+        })?;
+        Ok(found)
+    }
+
+    /// 将单个文件解压到 dst，dst 不能已存在
+    pub fn extract_file(&self, segments: &[String], dst: &Path) -> Result<()> {
+        self.extract_files(&[(segments.to_vec(), dst.to_path_buf())])
+    }
+
+    /// 一次流式读取将多个文件解压到各自的 dst，dst 不能已存在
+    ///
+    /// 同一路径出现多次时以最后一次为准，与入库时一致；有文件不存在时返回 [`Error::NotFound`]
+    pub fn extract_files(&self, files: &[(Vec<String>, PathBuf)]) -> Result<()> {
+        let mut targets: HashMap<&[String], Vec<&Path>> = HashMap::new();
+        for (segments, dst) in files {
+            targets
+                .entry(segments.as_slice())
+                .or_default()
+                .push(dst.as_path());
+        }
+        let mut written = HashSet::new();
+        self.visit(|segments, meta, reader| {
+            let Some(dsts) = targets.get(segments.as_slice()).filter(|_| !meta.is_dir) else {
+                return Ok(ControlFlow::Continue(()));
+            };
+            // 同一条目写入多个位置时只读取一次，其余从第一个位置复制
+            let [first, rest @ ..] = dsts.as_slice() else {
+                return Ok(ControlFlow::Continue(()));
+            };
+            std::io::copy(reader, &mut create_file(first, written.insert(*first))?)?;
+            for dst in rest {
+                let mut file = create_file(dst, written.insert(*dst))?;
+                std::io::copy(&mut File::open(first)?, &mut file)?;
+            }
+            Ok(ControlFlow::Continue(()))
+        })?;
+        match files
+            .iter()
+            .find(|(_, dst)| !written.contains(&dst.as_path()))
+        {
+            Some((segments, _)) => Err(Error::NotFound(format!(
+                "压缩包 {} 中的文件: {}",
+                self.path,
+                segments.join("/")
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// 将整个压缩包解压到 dest 目录，条目路径不能越出 dest
+    pub fn extract_all(&self, dest: &Path) -> Result<()> {
+        std::fs::create_dir_all(dest)?;
+        let file = File::open(&self.path)?;
+        match self.kind {
+            ArchiveKind::Zip => zip::ZipArchive::new(file)?.extract(dest)?,
+            ArchiveKind::Tar => tar::Archive::new(file).unpack(dest)?,
+            ArchiveKind::TarGz => tar::Archive::new(GzDecoder::new(file)).unpack(dest)?,
+        }
+        Ok(())
+    }
+
+    /// 流式读取所有条目并计算文件哈希，被忽略的条目跳过
+    ///
+    /// 同一路径出现多次时以最后一次为准，返回的条目按路径排序，上级目录在前
+    fn index(&self, ignore: &IgnoreRules, state: &ScanState) -> Result<Vec<SourceEntry>> {
+        let mut entries = BTreeMap::new();
+        self.visit(|segments, meta, reader| {
+            state.check()?;
+            if ignore.skips(&segments, meta.is_dir) {
+                return Ok(ControlFlow::Continue(()));
+            }
+            let display = Path::new(&self.path).join(segments.join("/"));
+            state.discovered(&display);
+
+            let hash = match meta.is_dir {
+                true => None,
+                false => Some(hash_reader(reader, &display, state)?),
+            };
+            entries.insert(
+                segments.clone(),
+                SourceEntry {
+                    path_segments: segments,
+                    is_dir: meta.is_dir,
+                    size: (!meta.is_dir).then_some(meta.size),
+                    mtime: meta.mtime,
+                    unix: None,
+                    hash,
+                },
+            );
+            Ok(ControlFlow::Continue(()))
+        })?;
+
+        // 压缩包中可以只记录文件而省略目录条目
+        let missing = entries
+            .keys()
+            .flat_map(|segments| (1..segments.len()).map(|depth| segments[..depth].to_vec()))
+            .filter(|dir| !entries.contains_key(dir))
+            .collect::<Vec<_>>();
+        for dir in missing {
+            entries.entry(dir.clone()).or_insert(SourceEntry {
+                path_segments: dir,
+                is_dir: true,
+                size: None,
+                mtime: None,
+                unix: None,
+                hash: None,
+            });
+        }
+        Ok(entries.into_values().collect())
+    }
+
+    /// 按顺序流式访问所有条目，f 返回 Break 时提前结束
+    ///
+    /// 路径不合法（含有 ".."）的条目与符号链接等特殊条目被跳过
+    fn visit(
+        &self,
+        mut f: impl FnMut(Vec<String>, EntryMeta, &mut dyn Read) -> Result<ControlFlow<()>>,
+    ) -> Result<()> {
+        let file = File::open(&self.path)?;
+        match self.kind {
+            ArchiveKind::Zip => {
+                let mut zip = zip::ZipArchive::new(file)?;
+                for i in 0..zip.len() {
+                    let mut entry = zip.by_index(i)?;
+                    let Some(segments) = entry_segments(entry.name()) else {
+                        log::warn!("跳过压缩包中不合法的路径: {}", entry.name());
+                        continue;
+                    };
+                    let meta = EntryMeta {
+                        is_dir: entry.is_dir(),
+                        size: entry.size(),
+                        mtime: Some(dos_millis(entry.last_modified())),
+                    };
+                    if f(segments, meta, &mut entry)?.is_break() {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+            ArchiveKind::Tar => visit_tar(file, f),
+            ArchiveKind::TarGz => visit_tar(GzDecoder::new(file), f),
+        }
+    }
+}
+
+/// 压缩包中的文件在枚举时即计算哈希，读取单个文件需要从头查找，不能以链接方式部署；
+/// 部署时同一个包的文件在一次读取中全部解压
+impl PackSource for Archive {
+    fn base_path(&self) -> &str {
+        &self.path
+    }
+
+    fn source_kind(&self) -> SourceKind {
+        SourceKind::Archive
+    }
+
+    fn archive_kind(&self) -> Option<ArchiveKind> {
+        Some(self.kind)
+    }
+
+    fn ignore_rules(&self) -> Result<IgnoreRules> {
+        match self.read_file(&[IGNORE_FILE.to_string()])? {
+            Some(data) => IgnoreRules::parse(&String::from_utf8_lossy(&data)),
+            None => Ok(IgnoreRules::new()),
+        }
+    }
+
+    fn entries(&self, ignore: &IgnoreRules, state: &ScanState) -> Result<Vec<SourceEntry>> {
+        self.index(ignore, state)
+    }
+
+    fn open(&self, segments: &[String]) -> Result<Box<dyn Read + Send + '_>> {
+        match self.read_file(segments)? {
+            Some(data) => Ok(Box::new(Cursor::new(data))),
+            None => Err(Error::NotFound(format!(
+                "压缩包 {} 中的文件: {}",
+                self.path,
+                segments.join("/")
+            ))),
+        }
+    }
+
+    fn extract(&self, files: &[(Vec<String>, PathBuf)]) -> Result<()> {
+        self.extract_files(files)
+    }
+}
+
+/// 按顺序流式访问 tar 中的条目
+fn visit_tar(
+    reader: impl Read,
+    mut f: impl FnMut(Vec<String>, EntryMeta, &mut dyn Read) -> Result<ControlFlow<()>>,
+) -> Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_type = entry.header().entry_type();
+        if !entry_type.is_file() && !entry_type.is_dir() {
+            log::debug!("跳过压缩包中的特殊条目: {:?}", entry.path());
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().to_string();
+        let Some(segments) = entry_segments(&name) else {
+            log::warn!("跳过压缩包中不合法的路径: {}", name);
+            continue;
+        };
+        let meta = EntryMeta {
+            is_dir: entry_type.is_dir(),
+            size: entry.header().size()?,
+            mtime: entry.header().mtime().ok().map(|secs| secs as i64 * 1000),
+        };
+        if f(segments, meta, &mut entry)?.is_break() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// 创建解压的目标文件，首次写入时目标不能已存在，重复的条目覆盖之前写入的内容
+fn create_file(dst: &Path, first: bool) -> std::io::Result<File> {
+    match first {
+        true => File::create_new(dst),
+        false => File::create(dst),
+    }
+}
+
+/// 条目路径的切片，含有 ".." 时返回 None
+fn entry_segments(name: &str) -> Option<Vec<String>> {
+    let segments = name
+        .split(|c| c == '/' || c == '\\')
+        .filter(|s| !s.is_empty() && *s != ".")
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+    match segments.is_empty() || segments.iter().any(|s| s == "..") {
+        true => None,
+        false => Some(segments),
+    }
+}
+
+/// zip 中的 DOS 时间没有时区，按 UTC 转换为毫秒时间戳
+fn dos_millis(t: zip::DateTime) -> i64 {
+    let (year, month, day) = (t.year() as i64, t.month() as i64, t.day() as i64);
+    // 公历日期到 1970-01-01 的天数，3 月为一年的第一个月
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    let secs = ((days * 24 + t.hour() as i64) * 60 + t.minute() as i64) * 60 + t.second() as i64;
+    secs * 1000
+}
@@ -0,0 +1,86 @@
+use duckdb::Connection;
+
+use crate::{ListPack, ListTree, Result, ScanOptions, transaction};
+
+/// 批量操作中的单项操作
+pub enum BatchOp<'a> {
+    /// 包的管理
+    Pack(ListPack<'a>),
+    /// 文件管理
+    Tree(ListTree<'a>),
+}
+
+impl<'a> From<ListPack<'a>> for BatchOp<'a> {
+    fn from(op: ListPack<'a>) -> Self {
+        Self::Pack(op)
+    }
+}
+
+impl<'a> From<ListTree<'a>> for BatchOp<'a> {
+    fn from(op: ListTree<'a>) -> Self {
+        Self::Tree(op)
+    }
+}
+
+/// 批量操作，所有操作在同一个事务中依次执行，任一操作失败则全部回滚
+#[derive(Default)]
+pub struct Batch<'a> {
+    ops: Vec<BatchOp<'a>>,
+    options: ScanOptions,
+}
+
+impl<'a> Batch<'a> {
+    /// 创建空的批量操作
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置插入与重新扫描包时使用的扫描选项
+    pub fn with_scan_options(mut self, options: ScanOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// 追加包的管理操作
+    pub fn pack(mut self, op: ListPack<'a>) -> Self {
+        self.ops.push(op.into());
+        self
+    }
+
+    /// 追加文件管理操作
+    pub fn tree(mut self, op: ListTree<'a>) -> Self {
+        self.ops.push(op.into());
+        self
+    }
+
+    /// 追加任意操作
+    pub fn push(&mut self, op: impl Into<BatchOp<'a>>) {
+        self.ops.push(op.into());
+    }
+
+    /// 操作数量
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// 是否没有任何操作
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// 在单个事务中执行所有操作
+    pub fn execute(&self, conn: &Connection) -> Result<()> {
+        if self.ops.is_empty() {
+            return Ok(());
+        }
+        transaction(conn, |conn| {
+            for op in &self.ops {
+                match op {
+                    BatchOp::Pack(op) => op.execute_in(conn, &self.options)?,
+                    BatchOp::Tree(op) => op.execute(conn)?,
+                }
+            }
+            Ok(())
+        })
+    }
+}
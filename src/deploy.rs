@@ -0,0 +1,1257 @@
+use std::{
+    collections::BTreeMap,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use duckdb::{Connection, OptionalExt, params};
+use hashbrown::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+
+use walkdir::WalkDir;
+
+use crate::{
+    DeployedRecord, Error, FileOverLinkList, InfoBase, ListPack, ListProfile, PackSource, Result,
+    ScanOptions, ViewOverTree, hash_file, model_insert::millis, pack_source,
+    profile::SQL_CURRENT_PROFILE, transaction,
+};
+
+/// 等待从来源写出的文件，按包id分组
+type PendingFiles<'a> = BTreeMap<
+    String,
+    (
+        Arc<dyn PackSource>,
+        Vec<(&'a PlannedFile, Vec<String>, PathBuf)>,
+    ),
+>;
+
+/// 部署方式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeployMethod {
+    /// 符号链接
+    #[default]
+    Symlink,
+    /// 硬链接，跨文件系统时自动退化为复制
+    Hardlink,
+    /// 复制
+    Copy,
+}
+
+impl DeployMethod {
+    /// 数据库中保存的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Symlink => "symlink",
+            Self::Hardlink => "hardlink",
+            Self::Copy => "copy",
+        }
+    }
+
+    /// 从数据库中保存的名称解析
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "symlink" => Some(Self::Symlink),
+            "hardlink" => Some(Self::Hardlink),
+            "copy" => Some(Self::Copy),
+            _ => None,
+        }
+    }
+}
+
+/// 部署结果
+#[derive(Debug, Default, Serialize, Clone)]
+pub struct DeployResult {
+    /// 新建的目录（相对路径）
+    pub created_dirs: Vec<String>,
+    /// 已部署的文件（相对路径）
+    pub deployed: Vec<String>,
+    /// 部署前已存在、被移入备份区的原始文件（相对路径）
+    pub backed_up: Vec<String>,
+    /// 目标位置被目录占用而跳过的文件（相对路径）
+    pub skipped: Vec<String>,
+}
+
+/// 执行部署计划的结果
+#[derive(Debug, Default, Serialize, Clone)]
+pub struct RedeploySummary {
+    /// 新建的目录（相对路径）
+    pub created_dirs: Vec<String>,
+    /// 新增部署的文件（相对路径）
+    pub added: Vec<String>,
+    /// 移除部署的文件（相对路径）
+    pub removed: Vec<String>,
+    /// 因变空而删除的目录（相对路径）
+    pub removed_dirs: Vec<String>,
+    /// 来源发生变化而重新部署的文件（相对路径）
+    pub replaced: Vec<String>,
+    /// 已部署且无变化的文件（相对路径）
+    pub unchanged: Vec<String>,
+    /// 部署前已存在、被移入备份区的原始文件（相对路径）
+    pub backed_up: Vec<String>,
+    /// 从备份区恢复的原始文件（相对路径）
+    pub restored: Vec<String>,
+    /// 目标位置被目录占用或部署后被修改而跳过的文件（相对路径）
+    pub skipped: Vec<String>,
+}
+
+/// 撤销部署的结果
+#[derive(Debug, Default, Serialize, Clone)]
+pub struct UndeployResult {
+    /// 已删除的部署文件（相对路径）
+    pub removed: Vec<String>,
+    /// 已删除的目录（相对路径）
+    pub removed_dirs: Vec<String>,
+    /// 从备份区恢复的原始文件（相对路径）
+    pub restored: Vec<String>,
+    /// 部署后被用户修改而保留的文件（相对路径）
+    pub modified: Vec<String>,
+}
+
+/// 部署计划中跳过某个路径的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// 目标位置被目录占用
+    OccupiedByDir,
+    /// 目标位置已有备份，无法再次备份
+    BackupExists,
+    /// 部署后被用户修改
+    Modified,
+}
+
+/// 部署计划中待部署的文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedFile {
+    /// 相对路径
+    pub path: String,
+    /// 来源文件id
+    pub file_id: String,
+    /// 来源包id
+    pub pack_id: String,
+    /// 来源文件绝对路径，压缩包来源时为压缩包路径与包内路径的拼接
+    pub source: String,
+    /// 来源文件哈希
+    pub hash: Option<String>,
+}
+
+impl From<&FileOverLinkList> for PlannedFile {
+    fn from(item: &FileOverLinkList) -> Self {
+        Self {
+            path: item.path_relative.clone(),
+            file_id: item.file_id.clone(),
+            pack_id: item.pack_id.clone(),
+            source: item.path_absolute.clone(),
+            hash: item.hash.clone(),
+        }
+    }
+}
+
+/// 部署计划中的单个操作，路径均为相对目标目录的路径
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum DeployAction {
+    /// 新建目录
+    CreateDir { path: String },
+    /// 将已存在的原始文件移入备份区
+    Backup { path: String },
+    /// 部署新文件
+    Place(PlannedFile),
+    /// 替换来源已变化的部署文件
+    Replace(PlannedFile),
+    /// 已部署且无变化
+    Keep { path: String },
+    /// 移除已不在覆盖树中的部署文件
+    Remove { path: String },
+    /// 将备份的原始文件放回原位
+    Restore { path: String },
+    /// 删除部署时新建且将变空的目录
+    RemoveDir { path: String },
+    /// 跳过
+    Skip { path: String, reason: SkipReason },
+}
+
+impl DeployAction {
+    /// 操作对应的相对路径
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Place(file) | Self::Replace(file) => &file.path,
+            Self::CreateDir { path }
+            | Self::Backup { path }
+            | Self::Keep { path }
+            | Self::Remove { path }
+            | Self::Restore { path }
+            | Self::RemoveDir { path }
+            | Self::Skip { path, .. } => path,
+        }
+    }
+}
+
+/// 部署计划，可序列化后交给前端预览确认，再通过 [`Deployer::apply`] 执行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeployPlan {
+    /// 部署目标根目录
+    pub target: String,
+    /// 部署方式
+    pub method: DeployMethod,
+    /// 按执行顺序排列的操作
+    pub actions: Vec<DeployAction>,
+}
+
+/// 收集未跟踪文件的结果
+#[derive(Debug, Default, Serialize, Clone)]
+pub struct CaptureResult {
+    /// 收集目录对应的包id
+    pub pack_id: String,
+    /// 移入收集目录的文件（相对路径）
+    pub files: Vec<String>,
+}
+
+/// 部署器，将覆盖树的顶层文件部署到目标目录
+#[derive(Debug, Clone)]
+pub struct Deployer {
+    /// 部署目标根目录
+    target: PathBuf,
+    /// 部署方式
+    method: DeployMethod,
+    /// 原始文件备份目录
+    backup: PathBuf,
+    /// 指定的包来源（包id -> 来源），未指定的包从数据库中读取
+    sources: HashMap<String, Arc<dyn PackSource>>,
+}
+
+impl Deployer {
+    const SQL_INSERT_RECORD: &'static str = r#"
+        INSERT OR REPLACE INTO DeployedFile
+            (target_root, path, file_id, pack_id, source_path, method, hash, size, mtime)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?);
+        "#;
+
+    const SQL_DELETE_RECORD: &'static str =
+        "DELETE FROM DeployedFile WHERE target_root = ? AND path = ?;";
+
+    const SQL_INSERT_DIR: &'static str =
+        "INSERT OR IGNORE INTO DeployedDir (target_root, path) VALUES (?, ?);";
+
+    const SQL_GET_DIRS: &'static str =
+        "SELECT path FROM DeployedDir WHERE target_root = ? ORDER BY length(path) DESC;";
+
+    const SQL_DELETE_DIR: &'static str =
+        "DELETE FROM DeployedDir WHERE target_root = ? AND path = ?;";
+
+    const SQL_INSERT_BACKUP: &'static str = r#"
+        INSERT INTO DeployBackup (target_root, path, backup_path, hash)
+        VALUES (?, ?, ?, ?);
+        "#;
+
+    const SQL_GET_BACKUP: &'static str =
+        "SELECT backup_path FROM DeployBackup WHERE target_root = ? AND path = ?;";
+
+    const SQL_GET_BACKUPS: &'static str =
+        "SELECT path FROM DeployBackup WHERE target_root = ? ORDER BY path;";
+
+    const SQL_DELETE_BACKUP: &'static str =
+        "DELETE FROM DeployBackup WHERE target_root = ? AND path = ?;";
+
+    const SQL_START_SESSION: &'static str =
+        "INSERT OR IGNORE INTO DeployTarget (target_root, session_start) VALUES (?, ?);";
+
+    const SQL_RESET_SESSION: &'static str =
+        "INSERT OR REPLACE INTO DeployTarget (target_root, session_start) VALUES (?, ?);";
+
+    const SQL_GET_SESSION: &'static str =
+        "SELECT session_start FROM DeployTarget WHERE target_root = ?;";
+
+    const SQL_END_SESSION: &'static str = "DELETE FROM DeployTarget WHERE target_root = ?;";
+
+    /// 创建新的部署器，参数为部署目标根目录，默认使用符号链接
+    ///
+    /// 原始文件默认备份到与目标目录同级的 `<目标目录名>.vfsbackup` 目录
+    pub fn new(target: &str) -> Self {
+        Self {
+            target: PathBuf::from(target),
+            method: DeployMethod::default(),
+            backup: PathBuf::from(format!(
+                "{}.vfsbackup",
+                target.trim_end_matches(['/', '\\'])
+            )),
+            sources: HashMap::new(),
+        }
+    }
+
+    /// 以当前方案的部署目标创建，方案未设置部署目标时返回 [`Error::InvalidInput`]
+    pub fn from_profile(conn: &Connection) -> Result<Self> {
+        let profile = ListProfile::current(conn)?;
+        match profile.target_root {
+            Some(target) => Ok(Self::new(&target)),
+            None => Err(Error::InvalidInput(format!(
+                "方案未设置部署目标: {}",
+                profile.name
+            ))),
+        }
+    }
+
+    /// 设置部署方式
+    pub fn with_method(mut self, method: DeployMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// 设置原始文件备份目录
+    pub fn with_backup_dir(mut self, backup: &str) -> Self {
+        self.backup = PathBuf::from(backup);
+        self
+    }
+
+    /// 指定包的来源，用于无法从数据库中恢复的来源（如内存来源）
+    pub fn with_source(mut self, pack_id: &str, source: Arc<dyn PackSource>) -> Self {
+        self.sources.insert(pack_id.to_string(), source);
+        self
+    }
+
+    /// 获取部署目标根目录
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    /// 部署记录中保存的目标根目录
+    fn target_root(&self) -> String {
+        self.target.to_string_lossy().to_string()
+    }
+
+    /// 获取相对路径在目标目录中的位置
+    ///
+    /// 路径为空或含有 `..`、根目录、盘符等可能越出目标目录的部分时返回 [`Error::InvalidInput`]
+    fn target_path(&self, relative: &str) -> Result<PathBuf> {
+        let invalid = || Error::InvalidInput(format!("路径不在部署目标目录内: {}", relative));
+        if relative.starts_with(['/', '\\']) {
+            return Err(invalid());
+        }
+        let mut path = self.target.clone();
+        for segment in relative
+            .split(['/', '\\'])
+            .filter(|s| !s.is_empty() && *s != ".")
+        {
+            let mut components = Path::new(segment).components();
+            match (components.next(), components.next()) {
+                (Some(Component::Normal(_)), None) => path.push(segment),
+                _ => return Err(invalid()),
+            }
+        }
+        if path == self.target {
+            return Err(invalid());
+        }
+        Ok(path)
+    }
+
+    /// 获取当前目标目录的部署记录
+    pub fn records(&self, conn: &Connection) -> Result<Vec<DeployedRecord>> {
+        let mut stmt = conn.prepare(DeployedRecord::SQL_GET_BY_TARGET)?;
+        DeployedRecord::get_res(&mut stmt, &self.target_root())
+    }
+
+    /// 生成部署计划：部署覆盖树中所有启用的顶层文件，不处理已不在覆盖树中的旧部署
+    pub fn plan_deploy(&self, conn: &Connection) -> Result<DeployPlan> {
+        self.build_plan(conn, false)
+    }
+
+    /// 生成增量重新部署计划：对比上次的部署记录与当前覆盖树，只处理发生变化的路径
+    pub fn plan_redeploy(&self, conn: &Connection) -> Result<DeployPlan> {
+        self.build_plan(conn, true)
+    }
+
+    /// 根据覆盖树与目标目录的当前状态生成部署计划，不修改磁盘和数据库
+    fn build_plan(&self, conn: &Connection, prune: bool) -> Result<DeployPlan> {
+        let list = ViewOverTree::GetAllPaths
+            .execute(conn)?
+            .as_file_over_link_list();
+        let mut records = self
+            .records(conn)?
+            .into_iter()
+            .map(|r| (r.path.clone(), r))
+            .collect::<HashMap<_, _>>();
+        let backups = self.backups(conn)?.into_iter().collect::<HashSet<_>>();
+
+        let mut plan = DeployPlan {
+            target: self.target_root(),
+            method: self.method,
+            actions: vec![],
+        };
+        // 计划中将被创建的目录
+        let mut planned_dirs = HashSet::new();
+        // 执行计划后仍有文件的目录
+        let mut filled_dirs = HashSet::new();
+
+        for item in &list {
+            let path = item.path_relative.clone();
+            let dst = self.target_path(&path)?;
+
+            match records.remove(&path) {
+                // 来源未变化，且磁盘上的文件完好
+                Some(record)
+                    if record.file_id == item.file_id
+                        && record.source_path == item.path_absolute
+                        && is_deployed_as(&dst, &record) =>
+                {
+                    plan.actions.push(DeployAction::Keep { path });
+                }
+                // 部署后被修改
+                Some(record)
+                    if dst.symlink_metadata().is_ok() && !is_deployed_as(&dst, &record) =>
+                {
+                    plan.actions.push(DeployAction::Skip {
+                        path,
+                        reason: SkipReason::Modified,
+                    });
+                    continue;
+                }
+                // 来源变化或已被删除，重新部署
+                Some(_) => {
+                    self.plan_dirs(&dst, &mut planned_dirs, &mut plan.actions);
+                    plan.actions.push(DeployAction::Replace(item.into()));
+                }
+                // 新增的路径
+                None => match dst.symlink_metadata() {
+                    Ok(meta) if meta.is_dir() => {
+                        plan.actions.push(DeployAction::Skip {
+                            path,
+                            reason: SkipReason::OccupiedByDir,
+                        });
+                        continue;
+                    }
+                    Ok(_) if backups.contains(&path) => {
+                        plan.actions.push(DeployAction::Skip {
+                            path,
+                            reason: SkipReason::BackupExists,
+                        });
+                        continue;
+                    }
+                    Ok(_) => {
+                        plan.actions.push(DeployAction::Backup { path });
+                        plan.actions.push(DeployAction::Place(item.into()));
+                    }
+                    Err(_) => {
+                        self.plan_dirs(&dst, &mut planned_dirs, &mut plan.actions);
+                        plan.actions.push(DeployAction::Place(item.into()));
+                    }
+                },
+            }
+            filled_dirs.extend(dst.ancestors().skip(1).map(Path::to_path_buf));
+        }
+
+        if !prune {
+            return Ok(plan);
+        }
+
+        // 不再属于覆盖树的路径
+        let mut stale = records.into_iter().collect::<Vec<_>>();
+        stale.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut removed = HashSet::new();
+        for (path, record) in stale {
+            let dst = self.target_path(&path)?;
+            if dst.symlink_metadata().is_ok() && !is_deployed_as(&dst, &record) {
+                plan.actions.push(DeployAction::Skip {
+                    path,
+                    reason: SkipReason::Modified,
+                });
+                filled_dirs.extend(dst.ancestors().skip(1).map(Path::to_path_buf));
+                continue;
+            }
+            plan.actions
+                .push(DeployAction::Remove { path: path.clone() });
+            if backups.contains(&path) {
+                plan.actions.push(DeployAction::Restore { path });
+                filled_dirs.extend(dst.ancestors().skip(1).map(Path::to_path_buf));
+            } else {
+                removed.insert(dst);
+            }
+        }
+
+        // 部署时新建、执行计划后将变空的目录，子目录先于父目录
+        for path in self.created_dirs(conn)? {
+            let dir = self.target_path(&path)?;
+            if filled_dirs.contains(&dir) {
+                continue;
+            }
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            let empty = entries
+                .filter_map(|e| e.ok())
+                .all(|e| removed.contains(&e.path()));
+            if empty {
+                removed.insert(dir);
+                plan.actions.push(DeployAction::RemoveDir { path });
+            }
+        }
+
+        Ok(plan)
+    }
+
+    /// 计划创建 dst 所缺失的上级目录
+    fn plan_dirs(
+        &self,
+        dst: &Path,
+        planned: &mut HashSet<PathBuf>,
+        actions: &mut Vec<DeployAction>,
+    ) {
+        let Some(parent) = dst.parent() else {
+            return;
+        };
+        let mut missing = parent
+            .ancestors()
+            .take_while(|p| *p != self.target && !planned.contains(*p) && !p.exists())
+            .collect::<Vec<_>>();
+        missing.reverse();
+
+        for p in missing {
+            if let Ok(relative) = p.strip_prefix(&self.target) {
+                actions.push(DeployAction::CreateDir {
+                    path: relative.to_string_lossy().replace('\\', "/"),
+                });
+            }
+            planned.insert(p.to_path_buf());
+        }
+    }
+
+    /// 执行部署计划
+    ///
+    /// 执行时会重新检查磁盘状态，计划生成后发生变化的路径会被跳过；
+    /// 计划中的路径越出目标目录，或待部署的文件与当前覆盖树不一致时不执行任何操作，
+    /// 返回 [`Error::InvalidInput`]；来源包无法从数据库中恢复且没有指定来源时同样不执行
+    pub fn apply(&self, conn: &Connection, plan: &DeployPlan) -> Result<RedeploySummary> {
+        if plan.target != self.target_root() {
+            return Err(Error::InvalidInput(format!(
+                "部署计划的目标目录不匹配: {}",
+                plan.target
+            )));
+        }
+        self.check_plan(conn, plan)?;
+
+        let records = self
+            .records(conn)?
+            .into_iter()
+            .map(|r| (r.path.clone(), r))
+            .collect::<HashMap<_, _>>();
+
+        std::fs::create_dir_all(&self.target)?;
+        conn.execute(
+            Self::SQL_START_SESSION,
+            params![self.target_root(), now_millis()],
+        )?;
+
+        let mut summary = RedeploySummary::default();
+        let mut pending = PendingFiles::new();
+        for action in &plan.actions {
+            match action {
+                DeployAction::CreateDir { path } => {
+                    let dir = self.target_path(path)?;
+                    self.create_dirs(conn, &dir, &mut summary.created_dirs)?;
+                }
+                DeployAction::Backup { path } => {
+                    let dst = self.target_path(path)?;
+                    if dst.symlink_metadata().is_ok() && self.backup(conn, &dst, path)? {
+                        summary.backed_up.push(path.clone());
+                    }
+                }
+                DeployAction::Place(file) => {
+                    let dst = self.target_path(&file.path)?;
+                    if dst.symlink_metadata().is_ok() {
+                        log::warn!("部署目标已存在，跳过: {}", dst.display());
+                        summary.skipped.push(file.path.clone());
+                        continue;
+                    }
+                    if let Some(parent) = dst.parent() {
+                        self.create_dirs(conn, parent, &mut summary.created_dirs)?;
+                    }
+                    self.place(conn, file, &dst, plan.method, &mut pending)?;
+                    summary.added.push(file.path.clone());
+                }
+                DeployAction::Replace(file) => {
+                    let dst = self.target_path(&file.path)?;
+                    if let Some(record) = records.get(&file.path) {
+                        if !self.remove_deployed(conn, &dst, record)? {
+                            summary.skipped.push(file.path.clone());
+                            continue;
+                        }
+                    }
+                    if dst.symlink_metadata().is_ok() {
+                        log::warn!("部署目标已存在，跳过: {}", dst.display());
+                        summary.skipped.push(file.path.clone());
+                        continue;
+                    }
+                    if let Some(parent) = dst.parent() {
+                        self.create_dirs(conn, parent, &mut summary.created_dirs)?;
+                    }
+                    self.place(conn, file, &dst, plan.method, &mut pending)?;
+                    summary.replaced.push(file.path.clone());
+                }
+                DeployAction::Keep { path } => {
+                    summary.unchanged.push(path.clone());
+                }
+                DeployAction::Remove { path } => {
+                    let Some(record) = records.get(path) else {
+                        continue;
+                    };
+                    let dst = self.target_path(path)?;
+                    if self.remove_deployed(conn, &dst, record)? {
+                        summary.removed.push(path.clone());
+                    } else {
+                        summary.skipped.push(path.clone());
+                    }
+                }
+                DeployAction::Restore { path } => {
+                    if self.restore(conn, path)? {
+                        summary.restored.push(path.clone());
+                    }
+                }
+                DeployAction::RemoveDir { path } => {
+                    if self.remove_dir_if_empty(conn, path)? {
+                        summary.removed_dirs.push(path.clone());
+                    }
+                }
+                DeployAction::Skip { path, .. } => {
+                    summary.skipped.push(path.clone());
+                }
+            }
+        }
+        self.extract_pending(conn, pending)?;
+
+        self.sync_state(conn)?;
+        Ok(summary)
+    }
+
+    /// 校验计划中的路径都在目标目录内，待部署的文件与当前覆盖树的顶层文件一致，
+    /// 且所有来源包都可以读取；只能删除部署时新建的目录，只能备份随后会被部署文件占用的路径
+    ///
+    /// 计划可能经过前端序列化，不能信任其中的路径与来源
+    fn check_plan(&self, conn: &Connection, plan: &DeployPlan) -> Result<()> {
+        let tree = ViewOverTree::GetAllPaths
+            .execute(conn)?
+            .as_file_over_link_list()
+            .into_iter()
+            .map(|item| (item.path_relative.clone(), PlannedFile::from(&item)))
+            .collect::<HashMap<_, _>>();
+        let created = self.created_dirs(conn)?.into_iter().collect::<HashSet<_>>();
+        // 每个路径最后一次放置文件的位置，备份只能发生在放置之前
+        let placed = plan
+            .actions
+            .iter()
+            .enumerate()
+            .filter(|(_, a)| matches!(a, DeployAction::Place(_) | DeployAction::Replace(_)))
+            .map(|(i, a)| (a.path(), i))
+            .collect::<HashMap<_, _>>();
+        let mut checked = HashSet::new();
+        for (i, action) in plan.actions.iter().enumerate() {
+            self.target_path(action.path())?;
+            let file = match action {
+                DeployAction::Place(file) | DeployAction::Replace(file) => file,
+                DeployAction::RemoveDir { path } if !created.contains(path) => {
+                    return Err(Error::InvalidInput(format!(
+                        "部署计划删除的目录不是部署时新建的: {}",
+                        path
+                    )));
+                }
+                DeployAction::Backup { path }
+                    if !placed.get(path.as_str()).is_some_and(|&j| j > i) =>
+                {
+                    return Err(Error::InvalidInput(format!(
+                        "部署计划备份的文件之后没有部署文件: {}",
+                        path
+                    )));
+                }
+                _ => continue,
+            };
+            let matches = tree.get(&file.path).is_some_and(|item| {
+                item.file_id == file.file_id
+                    && item.pack_id == file.pack_id
+                    && item.source == file.source
+                    && item.hash == file.hash
+            });
+            if !matches {
+                return Err(Error::InvalidInput(format!(
+                    "部署计划与当前覆盖树不一致，需要重新生成: {}",
+                    file.path
+                )));
+            }
+            // 自定义来源的包没有通过 with_source 指定时在写入磁盘前失败
+            if checked.insert(file.pack_id.as_str()) {
+                self.source(conn, &file.pack_id)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 包的来源，优先使用通过 [`Self::with_source`] 指定的来源
+    fn source(&self, conn: &Connection, pack_id: &str) -> Result<Arc<dyn PackSource>> {
+        match self.sources.get(pack_id) {
+            Some(source) => Ok(source.clone()),
+            None => pack_source(conn, pack_id),
+        }
+    }
+
+    /// 部署覆盖树中所有启用的顶层文件
+    pub fn deploy(&self, conn: &Connection) -> Result<DeployResult> {
+        let plan = self.plan_deploy(conn)?;
+        let summary = self.apply(conn, &plan)?;
+
+        Ok(DeployResult {
+            created_dirs: summary.created_dirs,
+            deployed: [summary.unchanged, summary.added, summary.replaced].concat(),
+            backed_up: summary.backed_up,
+            skipped: summary.skipped,
+        })
+    }
+
+    /// 增量重新部署：对比上次的部署记录与当前覆盖树，只处理发生变化的路径
+    pub fn redeploy(&self, conn: &Connection) -> Result<RedeploySummary> {
+        let plan = self.plan_redeploy(conn)?;
+        self.apply(conn, &plan)
+    }
+
+    /// 撤销部署：只删除本库创建且未被修改的文件，以及本库创建且已为空的目录
+    pub fn undeploy(&self, conn: &Connection) -> Result<UndeployResult> {
+        let mut result = UndeployResult::default();
+        for record in self.records(conn)? {
+            let dst = self.target_path(&record.path)?;
+            if self.remove_deployed(conn, &dst, &record)? {
+                result.removed.push(record.path);
+            } else {
+                // 被修改的文件交还给用户，不再由本库管理
+                conn.execute(
+                    Self::SQL_DELETE_RECORD,
+                    params![record.target_root, record.path],
+                )?;
+                result.modified.push(record.path);
+            }
+        }
+
+        // 恢复部署前的原始文件
+        for path in self.backups(conn)? {
+            if self.restore(conn, &path)? {
+                result.restored.push(path);
+            }
+        }
+
+        result.removed_dirs = self.prune_dirs(conn)?;
+        conn.execute(Self::SQL_END_SESSION, [self.target_root()])?;
+
+        self.sync_state(conn)?;
+        Ok(result)
+    }
+
+    /// 收集部署后由程序写入目标目录、不属于任何包的文件
+    ///
+    /// 文件被移入 pack_dir 并以最高优先级注册为包，之后重新部署即可链接回目标目录；
+    /// pack_dir 已注册为包时重新扫描该包，info 被忽略
+    ///
+    /// 目标目录没有部署会话（尚未部署或已撤销部署）时无法区分原有文件，返回 [`Error::NotFound`]；
+    /// 注册失败时已移动的文件放回目标目录
+    pub fn capture(
+        &self,
+        conn: &Connection,
+        pack_dir: &str,
+        info: InfoBase,
+    ) -> Result<CaptureResult> {
+        let records = self
+            .records(conn)?
+            .into_iter()
+            .map(|r| r.path)
+            .collect::<HashSet<_>>();
+        let session_start = conn
+            .query_row(Self::SQL_GET_SESSION, [self.target_root()], |row| {
+                row.get::<_, i64>(0)
+            })
+            .optional()?
+            .ok_or_else(|| Error::NotFound(format!("部署会话: {}", self.target_root())))?;
+
+        // 会话开始后写入且没有部署记录的文件
+        let files = WalkDir::new(&self.target)
+            .follow_links(false)
+            .min_depth(1)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| !e.file_type().is_dir())
+            .filter_map(|e| {
+                let relative = e.path().strip_prefix(&self.target).ok()?;
+                let relative = relative.to_string_lossy().replace('\\', "/");
+                if records.contains(&relative) {
+                    return None;
+                }
+                let modified = e.metadata().ok()?.modified().ok()?;
+                (millis(modified) >= session_start).then_some(relative)
+            })
+            .collect::<Vec<_>>();
+
+        let root = PathBuf::from(pack_dir);
+        let mut moved = vec![];
+        let res = self
+            .move_captured(&files, &root, &mut moved)
+            .and_then(|_| transaction(conn, |conn| self.register_captured(conn, pack_dir, info)));
+        match res {
+            Ok(pack_id) => Ok(CaptureResult { pack_id, files }),
+            Err(e) => {
+                for (from, to) in moved.iter().rev() {
+                    if let Err(err) = move_file(to, from) {
+                        log::error!("收集的文件放回失败 {}: {}", from.display(), err);
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// 将收集的文件移入 root，moved 记录已移动的 (原位置, 新位置)
+    fn move_captured(
+        &self,
+        files: &[String],
+        root: &Path,
+        moved: &mut Vec<(PathBuf, PathBuf)>,
+    ) -> Result<()> {
+        for relative in files {
+            let from = self.target_path(relative)?;
+            let to = root.join(relative);
+            if let Some(parent) = to.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            move_file(&from, &to)?;
+            moved.push((from, to));
+        }
+        std::fs::create_dir_all(root)?;
+        Ok(())
+    }
+
+    /// 注册或重新扫描收集目录，在当前方案中置为最高优先级并启用，返回包id
+    fn register_captured(
+        &self,
+        conn: &Connection,
+        pack_dir: &str,
+        info: InfoBase,
+    ) -> Result<String> {
+        let existing = conn
+            .query_row(
+                "SELECT id FROM FNPack WHERE base_path = ?;",
+                [pack_dir],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        let options = ScanOptions::default();
+        let pack_id = match existing {
+            Some(id) => {
+                ListPack::Rescan(&id).execute_in(conn, &options)?;
+                id
+            }
+            None => {
+                let id = info.id.to_string();
+                ListPack::Insert(pack_dir, info).execute_in(conn, &options)?;
+                id
+            }
+        };
+
+        ListPack::SetPriority(&pack_id, ListPack::next_priority(conn)?).execute(conn)?;
+        ListPack::SetActive(&pack_id, true).execute(conn)?;
+        conn.execute(
+            Self::SQL_RESET_SESSION,
+            params![self.target_root(), now_millis()],
+        )?;
+        Ok(pack_id)
+    }
+
+    /// 将目标位置已存在的原始文件移入备份区并记录
+    ///
+    /// 目标位置为目录或已有备份时不处理，返回 false
+    fn backup(&self, conn: &Connection, dst: &Path, relative: &str) -> Result<bool> {
+        if dst.symlink_metadata()?.is_dir() {
+            log::warn!("部署目标为目录，跳过: {}", dst.display());
+            return Ok(false);
+        }
+        if self.backup_path(conn, relative)?.is_some() {
+            log::warn!("部署目标已有备份，跳过: {}", dst.display());
+            return Ok(false);
+        }
+
+        let backup = self.backup.join(
+            dst.strip_prefix(&self.target)
+                .unwrap_or_else(|_| Path::new(relative)),
+        );
+        if let Some(parent) = backup.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let hash = match dst.symlink_metadata()? {
+            meta if meta.is_file() => Some(hash_file(dst, meta.len())?),
+            _ => None,
+        };
+        move_file(dst, &backup)?;
+
+        conn.execute(
+            Self::SQL_INSERT_BACKUP,
+            params![
+                self.target_root(),
+                relative,
+                backup.to_string_lossy().to_string(),
+                hash
+            ],
+        )?;
+        Ok(true)
+    }
+
+    /// 获取当前目标目录中有备份的路径（相对路径）
+    fn backups(&self, conn: &Connection) -> Result<Vec<String>> {
+        let mut stmt = conn.prepare(Self::SQL_GET_BACKUPS)?;
+        let paths = stmt
+            .query_map([self.target_root()], |row| row.get::<_, String>(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(paths)
+    }
+
+    /// 获取相对路径对应的备份文件位置
+    fn backup_path(&self, conn: &Connection, relative: &str) -> Result<Option<PathBuf>> {
+        let mut stmt = conn.prepare(Self::SQL_GET_BACKUP)?;
+        let path = stmt
+            .query_map(params![self.target_root(), relative], |row| {
+                row.get::<_, String>(0)
+            })?
+            .filter_map(|r| r.ok())
+            .next();
+        Ok(path.map(PathBuf::from))
+    }
+
+    /// 将备份的原始文件放回目标位置，目标位置被占用时保留备份并返回 false
+    fn restore(&self, conn: &Connection, relative: &str) -> Result<bool> {
+        let Some(backup) = self.backup_path(conn, relative)? else {
+            return Ok(false);
+        };
+        let dst = self.target_path(relative)?;
+        if dst.symlink_metadata().is_ok() {
+            log::warn!("恢复位置已被占用，保留备份: {}", dst.display());
+            return Ok(false);
+        }
+
+        if let Some(parent) = dst.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        move_file(&backup, &dst)?;
+
+        conn.execute(
+            Self::SQL_DELETE_BACKUP,
+            params![self.target_root(), relative],
+        )?;
+        Ok(true)
+    }
+
+    /// 移除一个已部署的文件及其部署记录
+    ///
+    /// 文件已不存在时视为移除成功；文件在部署后被修改时不删除，返回 false
+    fn remove_deployed(
+        &self,
+        conn: &Connection,
+        dst: &Path,
+        record: &DeployedRecord,
+    ) -> Result<bool> {
+        if dst.symlink_metadata().is_ok() {
+            if !is_deployed_as(dst, record) {
+                log::warn!("部署文件已被修改，保留: {}", dst.display());
+                return Ok(false);
+            }
+            std::fs::remove_file(dst)?;
+        }
+
+        conn.execute(
+            Self::SQL_DELETE_RECORD,
+            params![record.target_root, record.path],
+        )?;
+        Ok(true)
+    }
+
+    /// 按部署方式放置单个文件并写入部署记录
+    ///
+    /// 来源没有本地文件（如压缩包）时只能写入目标位置，总是以复制方式部署；
+    /// 这些文件先按包加入 pending，由 [`Self::extract_pending`] 统一写出
+    fn place<'a>(
+        &self,
+        conn: &Connection,
+        file: &'a PlannedFile,
+        dst: &Path,
+        method: DeployMethod,
+        pending: &mut PendingFiles<'a>,
+    ) -> Result<()> {
+        let source = self.source(conn, &file.pack_id)?;
+        let segments = file.path.split('/').map(String::from).collect::<Vec<_>>();
+        match source.local_path(&segments) {
+            Some(src) => {
+                let method = place_file(&src, dst, method)?;
+                self.record(conn, file, dst, method)
+            }
+            None => {
+                pending
+                    .entry(file.pack_id.clone())
+                    .or_insert_with(|| (source, Vec::new()))
+                    .1
+                    .push((file, segments, dst.to_path_buf()));
+                Ok(())
+            }
+        }
+    }
+
+    /// 将没有本地文件的来源按包一次写出，再写入部署记录
+    ///
+    /// 压缩包只需顺序读取一遍，文件内容直接写入目标位置
+    fn extract_pending(&self, conn: &Connection, pending: PendingFiles<'_>) -> Result<()> {
+        for (source, files) in pending.into_values() {
+            let targets = files
+                .iter()
+                .map(|(_, segments, dst)| (segments.clone(), dst.clone()))
+                .collect::<Vec<_>>();
+            source.extract(&targets)?;
+            for (file, _, dst) in &files {
+                self.record(conn, file, dst, DeployMethod::Copy)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 写入单个文件的部署记录
+    fn record(
+        &self,
+        conn: &Connection,
+        file: &PlannedFile,
+        dst: &Path,
+        method: DeployMethod,
+    ) -> Result<()> {
+        // 复制与硬链接的文件可能与扫描时的源文件不同，记录实际内容的哈希与元数据
+        let (hash, size, mtime) = match method {
+            DeployMethod::Symlink => (file.hash.clone(), None, None),
+            _ => {
+                let meta = dst.symlink_metadata()?;
+                let mtime = meta.modified().ok().map(millis);
+                (Some(hash_file(dst, meta.len())?), Some(meta.len()), mtime)
+            }
+        };
+
+        conn.execute(
+            Self::SQL_INSERT_RECORD,
+            params![
+                self.target_root(),
+                file.path,
+                file.file_id,
+                file.pack_id,
+                file.source,
+                method.as_str(),
+                hash,
+                size,
+                mtime
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 逐级创建目录，并记录新建目录的相对路径
+    fn create_dirs(&self, conn: &Connection, dir: &Path, created: &mut Vec<String>) -> Result<()> {
+        let mut missing = dir
+            .ancestors()
+            .take_while(|p| *p != self.target && !p.exists())
+            .collect::<Vec<_>>();
+        missing.reverse();
+
+        for p in missing {
+            std::fs::create_dir(p)?;
+            if let Ok(relative) = p.strip_prefix(&self.target) {
+                let relative = relative.to_string_lossy().replace('\\', "/");
+                conn.execute(Self::SQL_INSERT_DIR, params![self.target_root(), relative])?;
+                created.push(relative);
+            }
+        }
+        Ok(())
+    }
+
+    /// 获取部署时新建的目录（相对路径），子目录排在父目录之前
+    fn created_dirs(&self, conn: &Connection) -> Result<Vec<String>> {
+        // 按路径长度倒序，保证子目录先于父目录处理
+        let mut stmt = conn.prepare(Self::SQL_GET_DIRS)?;
+        let dirs = stmt
+            .query_map([self.target_root()], |row| row.get::<_, String>(0))?
+            .collect::<duckdb::Result<_>>()?;
+        Ok(dirs)
+    }
+
+    /// 删除部署时新建且当前为空的目录，返回删除的目录（相对路径）
+    fn prune_dirs(&self, conn: &Connection) -> Result<Vec<String>> {
+        let mut removed = vec![];
+        for path in self.created_dirs(conn)? {
+            if self.remove_dir_if_empty(conn, &path)? {
+                removed.push(path);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// 目录为空时删除，目录已不存在时只清理记录
+    fn remove_dir_if_empty(&self, conn: &Connection, path: &str) -> Result<bool> {
+        let dir = self.target_path(path)?;
+        let removed = match std::fs::read_dir(&dir) {
+            Ok(mut entries) => {
+                if entries.next().is_some() {
+                    return Ok(false);
+                }
+                std::fs::remove_dir(&dir)?;
+                true
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+            Err(e) => return Err(e.into()),
+        };
+        conn.execute(Self::SQL_DELETE_DIR, params![self.target_root(), path])?;
+        Ok(removed)
+    }
+
+    /// 按磁盘上的实际文件同步 FileNode 与 PriorityPack 的部署状态
+    ///
+    /// 只处理本目标目录的部署记录：部署在其他目标目录的文件保持已部署，
+    /// 包的部署状态只写入当前方案
+    pub fn sync_state(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "UPDATE FileNode SET is_deployed = false
+            WHERE is_deployed
+              AND id NOT IN (SELECT file_id FROM DeployedFile WHERE target_root <> ?);",
+            [self.target_root()],
+        )?;
+
+        let mut packs = HashSet::new();
+        for record in self.records(conn)? {
+            let dst = self.target_path(&record.path)?;
+            if is_deployed_as(&dst, &record) {
+                conn.execute(
+                    "UPDATE FileNode SET is_deployed = true WHERE id = ?;",
+                    [&record.file_id],
+                )?;
+                packs.insert(record.pack_id);
+            }
+        }
+
+        conn.execute(
+            &format!(
+                "UPDATE PriorityPack SET is_deployed = false WHERE profile_id = {};",
+                SQL_CURRENT_PROFILE
+            ),
+            [],
+        )?;
+        for pack_id in packs {
+            conn.execute(
+                &format!(
+                    "UPDATE PriorityPack SET is_deployed = true
+                    WHERE profile_id = {} AND pack_id = ?;",
+                    SQL_CURRENT_PROFILE
+                ),
+                [&pack_id],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// 当前时间的毫秒时间戳
+fn now_millis() -> i64 {
+    millis(SystemTime::now())
+}
+
+/// 按部署方式放置文件，返回实际使用的部署方式
+fn place_file(src: &Path, dst: &Path, method: DeployMethod) -> std::io::Result<DeployMethod> {
+    match method {
+        DeployMethod::Symlink => symlink_file(src, dst).map(|_| DeployMethod::Symlink),
+        DeployMethod::Hardlink => match std::fs::hard_link(src, dst) {
+            Ok(()) => Ok(DeployMethod::Hardlink),
+            // 硬链接不能跨文件系统，退化为复制
+            Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+                log::info!("硬链接跨文件系统，改为复制: {}", dst.display());
+                std::fs::copy(src, dst).map(|_| DeployMethod::Copy)
+            }
+            Err(e) => Err(e),
+        },
+        DeployMethod::Copy => std::fs::copy(src, dst).map(|_| DeployMethod::Copy),
+    }
+}
+
+/// 移动文件，跨文件系统时改为复制后删除
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    match std::fs::rename(from, to) {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            std::fs::copy(from, to)?;
+            std::fs::remove_file(from)
+        }
+        res => res,
+    }
+}
+
+/// 判断 dst 是否仍是部署记录所描述的文件
+///
+/// 硬链接还要求与来源为同一文件；写入硬链接会同时修改包中的来源文件，因此与复制一样比对内容
+fn is_deployed_as(dst: &Path, record: &DeployedRecord) -> bool {
+    let src = Path::new(&record.source_path);
+    match DeployMethod::parse(&record.method) {
+        Some(DeployMethod::Symlink) => is_link_to(dst, src),
+        Some(DeployMethod::Hardlink) => {
+            is_same_file(dst, src).unwrap_or(true) && matches_content(dst, record)
+        }
+        Some(DeployMethod::Copy) => matches_content(dst, record),
+        None => false,
+    }
+}
+
+/// 判断 dst 是否为指向 src 的符号链接
+fn is_link_to(dst: &Path, src: &Path) -> bool {
+    match std::fs::read_link(dst) {
+        Ok(target) => target == src,
+        Err(_) => false,
+    }
+}
+
+/// 判断 dst 是否为内容与部署时相同的普通文件
+///
+/// 大小与记录不同时直接视为已修改，大小与修改时间都与记录相同时不再计算哈希
+fn matches_content(dst: &Path, record: &DeployedRecord) -> bool {
+    let Ok(meta) = dst.symlink_metadata() else {
+        return false;
+    };
+    if !meta.is_file() || record.size.is_some_and(|size| size != meta.len()) {
+        return false;
+    }
+    if record.size.is_some()
+        && record.mtime.is_some()
+        && record.mtime == meta.modified().ok().map(millis)
+    {
+        return true;
+    }
+    record
+        .hash
+        .as_deref()
+        .is_some_and(|hash| hash_file(dst, meta.len()).is_ok_and(|h| h == hash))
+}
+
+/// 判断两个路径是否为同一文件（硬链接），无法判断时返回 None
+#[cfg(unix)]
+fn is_same_file(a: &Path, b: &Path) -> Option<bool> {
+    use std::os::unix::fs::MetadataExt;
+    match (a.symlink_metadata(), b.metadata()) {
+        (Ok(a), Ok(b)) => Some(a.is_file() && a.dev() == b.dev() && a.ino() == b.ino()),
+        _ => Some(false),
+    }
+}
+
+/// 判断两个路径是否为同一文件（硬链接），无法判断时返回 None
+#[cfg(not(unix))]
+fn is_same_file(_a: &Path, _b: &Path) -> Option<bool> {
+    None
+}
+
+/// 创建文件符号链接
+fn symlink_file(src: &Path, dst: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(src, dst)
+    }
+    #[cfg(windows)]
+    {
+        std::os::windows::fs::symlink_file(src, dst)
+    }
+}
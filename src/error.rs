@@ -0,0 +1,96 @@
+use std::{fmt, io};
+
+/// 库内统一的错误类型
+#[derive(Debug)]
+pub enum Error {
+    /// 文件读写、遍历失败
+    Io(io::Error),
+    /// 数据库执行失败
+    Db(duckdb::Error),
+    /// 包、文件或记录不存在
+    NotFound(String),
+    /// 违反主键、唯一或外键等约束
+    Constraint(String),
+    /// 参数不合法
+    InvalidInput(String),
+    /// 操作被取消令牌中止
+    Cancelled,
+    /// 数据库由更新版本的库创建，无法安全打开
+    SchemaTooNew {
+        /// 数据库的表结构版本
+        found: i32,
+        /// 本库支持的最高版本
+        supported: i32,
+    },
+}
+
+/// 库内统一的返回类型
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "IO 错误: {}", e),
+            Self::Db(e) => write!(f, "数据库错误: {}", e),
+            Self::NotFound(s) => write!(f, "未找到: {}", s),
+            Self::Constraint(s) => write!(f, "约束冲突: {}", s),
+            Self::InvalidInput(s) => write!(f, "无效输入: {}", s),
+            Self::Cancelled => write!(f, "操作已取消"),
+            Self::SchemaTooNew { found, supported } => write!(
+                f,
+                "数据库版本 {} 高于支持的版本 {}，请升级后再打开",
+                found, supported
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Db(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<duckdb::Error> for Error {
+    fn from(e: duckdb::Error) -> Self {
+        match e {
+            duckdb::Error::QueryReturnedNoRows => Self::NotFound("查询没有返回结果".to_string()),
+            // duckdb 的约束错误信息以 "Constraint Error" 开头
+            e if e.to_string().contains("Constraint Error") => Self::Constraint(e.to_string()),
+            e => Self::Db(e),
+        }
+    }
+}
+
+impl From<walkdir::Error> for Error {
+    fn from(e: walkdir::Error) -> Self {
+        Self::Io(e.into())
+    }
+}
+
+impl From<uuid::Error> for Error {
+    fn from(e: uuid::Error) -> Self {
+        Self::InvalidInput(e.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(e: zip::result::ZipError) -> Self {
+        use zip::result::ZipError;
+        match e {
+            ZipError::Io(e) => Self::Io(e),
+            ZipError::FileNotFound => Self::NotFound(e.to_string()),
+            e => Self::InvalidInput(format!("无效的压缩包: {}", e)),
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use std::path::Path;
+
+use duckdb::Connection;
+use regex::Regex;
+
+use crate::{Error, Result};
+
+/// 包根目录下的忽略规则文件名
+pub const IGNORE_FILE: &str = ".vfsignore";
+
+/// 单条忽略规则
+#[derive(Debug, Clone)]
+struct IgnoreRule {
+    /// 匹配相对路径（以 '/' 分隔）的正则
+    regex: Regex,
+    /// 以 '!' 开头，重新包含被之前规则忽略的路径
+    negate: bool,
+    /// 以 '/' 结尾，只匹配目录
+    dir_only: bool,
+}
+
+/// gitignore 风格的忽略规则，后出现的规则优先
+///
+/// 支持 `*`、`?`、`[...]`、`**`、开头的 `!` 与 `/`、结尾的 `/`，
+/// 目录被忽略时其中的所有内容都被忽略
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    rules: Vec<IgnoreRule>,
+}
+
+impl IgnoreRules {
+    /// 创建空的规则集合
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 解析 gitignore 格式的文本，空行与 '#' 开头的行被跳过
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut rules = Self::new();
+        for line in text.lines() {
+            rules.add(line)?;
+        }
+        Ok(rules)
+    }
+
+    /// 读取包根目录下的 [`IGNORE_FILE`]，文件不存在时返回空规则
+    pub fn from_pack_root(root: &Path) -> Result<Self> {
+        match std::fs::read_to_string(root.join(IGNORE_FILE)) {
+            Ok(text) => Self::parse(&text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 读取数据库中的全局规则
+    pub fn global(conn: &Connection) -> Result<Self> {
+        let mut rules = Self::new();
+        for pattern in &ListIgnore::list(conn)? {
+            rules.add(pattern)?;
+        }
+        Ok(rules)
+    }
+
+    /// 添加一条规则，空行与注释被忽略
+    pub fn add(&mut self, pattern: &str) -> Result<()> {
+        if let Some(rule) = parse_rule(pattern)? {
+            self.rules.push(rule);
+        }
+        Ok(())
+    }
+
+    /// 追加另一组规则，追加的规则优先
+    pub fn extend(&mut self, other: IgnoreRules) {
+        self.rules.extend(other.rules);
+    }
+
+    /// 是否没有任何规则
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// 相对路径切片是否被忽略，祖先目录被忽略时同样视为忽略
+    pub fn is_ignored(&self, segments: &[String], is_dir: bool) -> bool {
+        if self.rules.is_empty() || segments.is_empty() {
+            return false;
+        }
+        let ancestor_ignored =
+            (1..segments.len()).any(|depth| self.matches(&segments[..depth].join("/"), true));
+        ancestor_ignored || self.matches(&segments.join("/"), is_dir)
+    }
+
+    /// 扫描包时是否跳过该路径，包根目录的规则文件本身也被跳过
+    pub(crate) fn skips(&self, segments: &[String], is_dir: bool) -> bool {
+        matches!(segments, [name] if name == IGNORE_FILE) || self.is_ignored(segments, is_dir)
+    }
+
+    /// 按最后一条匹配的规则判断路径是否被忽略
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        self.rules
+            .iter()
+            .rev()
+            .find(|r| (is_dir || !r.dir_only) && r.regex.is_match(path))
+            .is_some_and(|r| !r.negate)
+    }
+}
+
+/// 解析单条规则
+fn parse_rule(line: &str) -> Result<Option<IgnoreRule>> {
+    let line = line.trim_end();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+
+    let (negate, pattern) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        // "\!" 与 "\#" 按字面匹配
+        None if line.starts_with("\\!") || line.starts_with("\\#") => (false, &line[1..]),
+        None => (false, line),
+    };
+    let (dir_only, pattern) = match pattern.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, pattern),
+    };
+    // 开头或中间含有 '/' 时相对包根目录匹配，否则匹配任意层级
+    let anchored = pattern.contains('/');
+    let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+    if pattern.is_empty() {
+        return Err(Error::InvalidInput(format!("空的忽略规则: {}", line)));
+    }
+
+    let re = glob_regex(pattern, anchored);
+    let regex = Regex::new(&re)
+        .map_err(|e| Error::InvalidInput(format!("无效的忽略规则 {}: {}", line, e)))?;
+    Ok(Some(IgnoreRule {
+        regex,
+        negate,
+        dir_only,
+    }))
+}
+
+/// 将以 '/' 分隔的 glob 转换为完整匹配相对路径的正则
+///
+/// anchored 为 false 时可以匹配任意层级下的路径，生成的正则同时兼容 duckdb 的 RE2
+pub(crate) fn glob_regex(pattern: &str, anchored: bool) -> String {
+    let segments = pattern.split('/').collect::<Vec<_>>();
+    let mut re = String::from("^");
+    if !anchored {
+        re.push_str("(?:.*/)?");
+    }
+    for (i, segment) in segments.iter().enumerate() {
+        let last = i == segments.len() - 1;
+        if *segment == "**" {
+            re.push_str(if last { ".*" } else { "(?:.*/)?" });
+            continue;
+        }
+        re.push_str(&segment_regex(segment));
+        if !last {
+            re.push('/');
+        }
+    }
+    re.push('$');
+    re
+}
+
+/// 将单个路径段的通配符转换为正则
+fn segment_regex(segment: &str) -> String {
+    let chars = segment.chars().collect::<Vec<_>>();
+    let mut re = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' => {
+                // 段内连续的 '*' 等同于单个 '*'
+                while chars.get(i + 1) == Some(&'*') {
+                    i += 1;
+                }
+                re.push_str("[^/]*");
+            }
+            '?' => re.push_str("[^/]"),
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                re.push_str(&regex::escape(&chars[i].to_string()));
+            }
+            '[' => match chars[i + 1..].iter().position(|&c| c == ']') {
+                Some(len) if len > 0 => {
+                    let class = &chars[i + 1..i + 1 + len];
+                    re.push('[');
+                    let class = match class[0] {
+                        '!' | '^' => {
+                            re.push('^');
+                            &class[1..]
+                        }
+                        _ => class,
+                    };
+                    for &c in class {
+                        if matches!(c, '\\' | '[' | ']' | '^' | '&' | '~') {
+                            re.push('\\');
+                        }
+                        re.push(c);
+                    }
+                    re.push(']');
+                    i += len + 1;
+                }
+                // 没有闭合的 '[' 按字面匹配
+                _ => re.push_str(r"\["),
+            },
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    re
+}
+
+/// 全局忽略规则的管理
+pub enum ListIgnore<'a> {
+    /// 添加规则
+    Add(&'a str),
+    /// 移除规则
+    Remove(&'a str),
+    /// 清空所有规则
+    Clear,
+}
+
+impl ListIgnore<'_> {
+    /// 执行规则相关操作，新增的规则只对之后的扫描生效
+    pub fn execute(&self, conn: &Connection) -> Result<()> {
+        use ListIgnore::*;
+        match self {
+            Add(pattern) => {
+                let pattern = pattern.trim_end();
+                if parse_rule(pattern)?.is_none() {
+                    return Err(Error::InvalidInput(format!("空的忽略规则: {}", pattern)));
+                }
+                conn.execute("INSERT INTO IgnoreRule (pattern) VALUES (?);", [pattern])?;
+            }
+            Remove(pattern) => {
+                conn.execute("DELETE FROM IgnoreRule WHERE pattern = ?;", [*pattern])?;
+            }
+            Clear => {
+                conn.execute("DELETE FROM IgnoreRule;", [])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 获取所有全局规则，按生效顺序排列
+    pub fn list(conn: &Connection) -> Result<Vec<String>> {
+        let mut stmt = conn.prepare("SELECT pattern FROM IgnoreRule ORDER BY id;")?;
+        let patterns = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<duckdb::Result<Vec<_>>>()?;
+        Ok(patterns)
+    }
+}
@@ -1,24 +1,74 @@
-use std::{borrow::Cow, fmt::Display};
-
-use anyhow::Ok;
-use duckdb::Connection;
-
+use std::{borrow::Cow, sync::Arc};
+
+use duckdb::{AccessMode, Config, Connection, params, params_from_iter, types::Value};
+use parking_lot::Mutex;
+use profile::SQL_CURRENT_PROFILE;
+
+pub mod archive;
+pub mod batch;
+pub mod deploy;
+pub mod error;
+pub mod ignore;
+pub mod migration;
 pub mod model_insert;
 pub mod model_select;
-
+pub mod profile;
+pub mod progress;
+pub mod relation;
+pub mod rule;
+pub mod source;
+pub mod verify;
+
+pub use archive::{Archive, ArchiveKind};
+pub use batch::*;
+pub use deploy::*;
+pub use error::{Error, Result};
+pub use ignore::{IGNORE_FILE, IgnoreRules, ListIgnore};
 pub use model_insert::*;
 pub use model_select::*;
+pub use profile::ListProfile;
+pub use progress::{CancelToken, ScanOptions, ScanProgress, ScanState};
+pub use relation::{ListRelation, RelationIssue, RelationKind, SortReport, VersionReq};
+pub use rule::PatternKind;
+pub use source::{DirSource, MemorySource, PackSource, SourceEntry, SourceKind, pack_source};
+pub use verify::*;
 
-const SQL_INIT: &'static str = include_str!(r"..\sql\init\core.sql");
-const SQL_INIT_INFO: &'static str = include_str!(r"..\sql\init\info.sql");
 const SQL_VIEW_AOFS: &'static str = include_str!(r"..\sql\view\path_override_files.sql");
 const SQL_VIEW_PACK: &'static str = include_str!(r"..\sql\view\pack.sql");
 
+/// 在事务中执行 f，失败时回滚
+///
+/// duckdb 不支持嵌套事务，f 内部不能再开启事务
+pub(crate) fn transaction<T>(
+    conn: &Connection,
+    f: impl FnOnce(&Connection) -> Result<T>,
+) -> Result<T> {
+    conn.execute_batch("BEGIN TRANSACTION;")?;
+    match f(conn) {
+        Ok(v) => {
+            conn.execute_batch("COMMIT;")?;
+            Ok(v)
+        }
+        Err(e) => {
+            if let Err(rollback) = conn.execute_batch("ROLLBACK;") {
+                log::error!("事务回滚失败: {}", rollback);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// 生成由 n 个占位符组成的 VARCHAR[]，配合逐项绑定的参数使用
+pub(crate) fn list_placeholders(n: usize) -> String {
+    format!("[{}]::VARCHAR[]", vec!["?"; n].join(", "))
+}
+
 /// 文件数据库
 #[derive(Debug)]
 pub struct FNDB {
     path: String,
     instance_w: Option<Connection>,
+    read_only: bool,
 }
 
 impl FNDB {
@@ -27,47 +77,136 @@ impl FNDB {
         Self {
             path: path.to_string(),
             instance_w: None,
+            read_only: false,
         }
     }
 
-    /// 连接数据库（读写）
-    pub fn connect_rw(&mut self) {
+    /// 连接数据库（读写），连接后执行未应用的迁移
+    pub fn connect_rw(&mut self) -> Result<()> {
         // 只在此处引入 duckdb::Connection
-        self.instance_w = Some(duckdb::Connection::open(&self.path).unwrap());
+        let conn = duckdb::Connection::open(&self.path)?;
+        migration::migrate(&conn)?;
+        self.instance_w = Some(conn);
+        self.read_only = false;
+        Ok(())
     }
 
-    /// 获取数据库连接
+    /// 连接数据库（只读），不执行迁移
+    ///
+    /// 数据库版本高于本库支持的版本时拒绝打开
+    pub fn connect_ro(&mut self) -> Result<()> {
+        let config = Config::default().access_mode(AccessMode::ReadOnly)?;
+        let conn = duckdb::Connection::open_with_flags(&self.path, config)?;
+        let version = migration::schema_version(&conn)?;
+        if version > migration::SCHEMA_VERSION {
+            return Err(Error::SchemaTooNew {
+                found: version,
+                supported: migration::SCHEMA_VERSION,
+            });
+        }
+        if version < migration::SCHEMA_VERSION {
+            log::warn!(
+                "只读打开的数据库版本 {} 低于 {}，需要读写连接完成迁移",
+                version,
+                migration::SCHEMA_VERSION
+            );
+        }
+        self.instance_w = Some(conn);
+        self.read_only = true;
+        Ok(())
+    }
+
+    /// 是否为只读连接
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// 从当前连接克隆出共享同一数据库实例的新连接，供工作线程使用
+    pub fn try_clone_conn(&self) -> Result<Connection> {
+        Ok(self.try_conn()?.try_clone()?)
+    }
+
+    /// 获取可在线程间共享的句柄
+    pub fn handle(&self) -> Result<FNDBHandle> {
+        Ok(FNDBHandle {
+            path: self.path.as_str().into(),
+            read_only: self.read_only,
+            conn: Arc::new(Mutex::new(self.try_clone_conn()?)),
+        })
+    }
+
+    /// 获取数据库连接，未连接时 panic
+    #[deprecated(note = "未连接时 panic，请使用返回 Result 的 try_conn")]
     pub fn get_conn(&self) -> &duckdb::Connection {
-        self.instance_w.as_ref().unwrap()
+        self.try_conn().expect("数据库未连接")
+    }
+
+    /// 获取数据库连接
+    pub fn try_conn(&self) -> Result<&duckdb::Connection> {
+        self.instance_w
+            .as_ref()
+            .ok_or_else(|| Error::InvalidInput(format!("数据库未连接: {}", self.path)))
     }
 
     /// 断开数据库连接
-    pub fn disconnect_w(&mut self) {
-        if self.instance_w.is_none() {
-            return;
-        };
+    pub fn disconnect_w(&mut self) -> Result<()> {
+        self.read_only = false;
         if let Some(conn) = self.instance_w.take() {
-            conn.close().unwrap();
+            conn.close().map_err(|(_, e)| e)?;
         }
-        self.instance_w = None;
+        Ok(())
     }
 
-    /// 初始化数据库表结构
-    pub fn init(&self) {
+    /// 初始化数据库表结构，即执行所有未应用的迁移
+    pub fn init(&self) -> Result<()> {
         if self.instance_w.is_none() {
-            return;
+            return Ok(());
         }
-        self.get_conn().execute_batch(&SQL_INIT).unwrap();
-        self.get_conn().execute_batch(&SQL_INIT_INFO).unwrap();
+        migration::migrate(self.try_conn()?)?;
+        Ok(())
     }
 
-    /// 初始化视图
-    pub fn init_view(&self) {
-        if self.instance_w.is_none() {
-            return;
+    /// 初始化视图，只读连接时跳过
+    pub fn init_view(&self) -> Result<()> {
+        if self.instance_w.is_none() || self.read_only {
+            return Ok(());
         }
-        self.get_conn().execute_batch(&SQL_VIEW_AOFS).unwrap();
-        self.get_conn().execute_batch(&SQL_VIEW_PACK).unwrap();
+        self.try_conn()?.execute_batch(&SQL_VIEW_AOFS)?;
+        self.try_conn()?.execute_batch(&SQL_VIEW_PACK)?;
+        Ok(())
+    }
+}
+
+/// 可在线程间共享的数据库句柄
+///
+/// 克隆句柄的开销很小，各线程通过 [`Self::connect`] 获取独立连接，
+/// 或通过 [`Self::with_conn`] 加锁使用共享连接
+#[derive(Debug, Clone)]
+pub struct FNDBHandle {
+    path: Arc<str>,
+    read_only: bool,
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl FNDBHandle {
+    /// 数据库路径
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// 是否为只读连接
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// 克隆出共享同一数据库实例的新连接，供当前线程独占使用
+    pub fn connect(&self) -> Result<Connection> {
+        Ok(self.conn.lock().try_clone()?)
+    }
+
+    /// 加锁后在共享连接上执行 f
+    pub fn with_conn<T>(&self, f: impl FnOnce(&Connection) -> Result<T>) -> Result<T> {
+        f(&self.conn.lock())
     }
 }
 
@@ -85,6 +224,8 @@ pub enum ViewOverTree<'a> {
     GetHashEqualFiles(&'a str),
     /// 获取所有哈希冲突的文件
     GetAllHashEqualFiles,
+    /// 获取所有互为硬链接的文件
+    GetAllHardlinkFiles,
 }
 
 impl<'a> ViewOverTree<'a> {
@@ -98,7 +239,7 @@ impl<'a> ViewOverTree<'a> {
             top_file_active,
             top_is_deployed
         from deployed_files
-        where depth = 
+        where depth = ?
         "#
     };
 
@@ -113,6 +254,9 @@ impl<'a> ViewOverTree<'a> {
 
     const SQL_GET_ALL_HASH_EQUAL_FILES: &'static str =
         include_str!(r"..\sql\tool\get_hash_equal_group.sql");
+
+    const SQL_GET_ALL_HARDLINK_FILES: &'static str =
+        include_str!(r"..\sql\tool\get_hardlink_group.sql");
 }
 
 #[derive(Debug)]
@@ -163,61 +307,73 @@ impl ResultOverTree {
 }
 
 impl<'a> ViewOverTree<'a> {
-    pub fn execute(&self, conn: &duckdb::Connection) -> anyhow::Result<ResultOverTree> {
+    pub fn execute(&self, conn: &duckdb::Connection) -> Result<ResultOverTree> {
         let sql = self.to_sql();
         let mut stmt = conn.prepare(&sql)?;
+        let params = params_from_iter(self.params());
 
         // 根据变体选择解析函数
         match self {
             Self::GetTop | Self::GetChildren(_) => {
-                let result = FileOverTree::get_res(&mut stmt)?;
+                let result = FileOverTree::get_res(&mut stmt, params)?;
                 Ok(ResultOverTree::TreeList(result))
             }
             Self::GetAllPaths => {
-                let result = FileOverLinkList::get_res(&mut stmt)?;
+                let result = FileOverLinkList::get_res(&mut stmt, params)?;
                 Ok(ResultOverTree::LinkList(result))
             }
             Self::GetConflictFiles(_) => {
-                let result = ConflictFileList::get_res(&mut stmt)?;
+                let result = ConflictFileList::get_res(&mut stmt, params)?;
                 Ok(ResultOverTree::ConflictFileList(result))
             }
             Self::GetHashEqualFiles(_) => {
-                let result = HashEqualFiles::get_res(&mut stmt)?;
+                let result = HashEqualFiles::get_res(&mut stmt, params)?;
                 Ok(ResultOverTree::HashEqualFileList(result))
             }
-            Self::GetAllHashEqualFiles => {
-                let result = HashEqualFiles::get_res_group(&mut stmt)?;
+            Self::GetAllHashEqualFiles | Self::GetAllHardlinkFiles => {
+                let result = HashEqualFiles::get_res_group(&mut stmt, params)?;
                 Ok(ResultOverTree::HashEqualFileGroup(result))
             }
         }
     }
 
+    /// 生成带占位符的 SQL，参数由 [`Self::params`] 提供
     #[inline]
     pub fn to_sql(&self) -> Cow<'static, str> {
         use ViewOverTree::*;
         match self {
-            GetTop => (Self::SQL_GET_.to_string() + " 1").into(),
-            GetChildren(path_segments) => {
-                let base = (Self::SQL_GET_.to_string() + &(path_segments.len() + 1).to_string())
-                    .to_string();
-                let sql = format!(
-                    "{} and path_segments[:{}] = {:?}",
-                    base,
-                    path_segments.len(),
-                    path_segments
-                )
-                .replace("\"", "'");
-                sql.into()
-            }
-            GetAllPaths => Self::SQL_TOOL_GET_ALL_PATHS.into(),
-            GetConflictFiles(path) => format!(
-                "{} and relative_path = '{}'",
-                Self::SQL_GET_CONFLICT_FILES,
-                path
+            GetTop => Self::SQL_GET_.into(),
+            GetChildren(path_segments) => format!(
+                "{} and path_segments[:{}] = {}",
+                Self::SQL_GET_,
+                path_segments.len(),
+                list_placeholders(path_segments.len())
             )
             .into(),
-            GetHashEqualFiles(path) => Self::SQL_GET_HASH_EQUAL_FILES.replace('$', path).into(),
+            GetAllPaths => Self::SQL_TOOL_GET_ALL_PATHS.into(),
+            GetConflictFiles(_) => {
+                format!("{} and relative_path = ?", Self::SQL_GET_CONFLICT_FILES).into()
+            }
+            GetHashEqualFiles(_) => Self::SQL_GET_HASH_EQUAL_FILES.into(),
             GetAllHashEqualFiles => Self::SQL_GET_ALL_HASH_EQUAL_FILES.into(),
+            GetAllHardlinkFiles => Self::SQL_GET_ALL_HARDLINK_FILES.into(),
+        }
+    }
+
+    /// 与 [`Self::to_sql`] 中占位符对应的参数
+    pub fn params(&self) -> Vec<Value> {
+        use ViewOverTree::*;
+        match self {
+            GetTop => vec![Value::Int(1)],
+            GetChildren(path_segments) => {
+                let mut params = vec![Value::Int(path_segments.len() as i32 + 1)];
+                params.extend(path_segments.iter().cloned().map(Value::Text));
+                params
+            }
+            GetConflictFiles(path) => vec![Value::Text(path.to_string())],
+            // 文件id在 SQL 中出现两次
+            GetHashEqualFiles(id) => vec![Value::Text(id.to_string()), Value::Text(id.to_string())],
+            GetAllPaths | GetAllHashEqualFiles | GetAllHardlinkFiles => vec![],
         }
     }
 }
@@ -259,11 +415,16 @@ impl ViewPack<'_> {
         f.is_dir,
         f.size,
         f.hash,
-        f.is_active,
+        COALESCE(pf.is_active, TRUE) AS is_active,
         f.is_deployed,
-        f.depth
+        f.depth,
+        f.mtime,
+        f.inode,
+        f.mode
         FROM
         FileNode f
+        LEFT JOIN ProfileFile pf
+        ON pf.file_id = f.id AND pf.profile_id = (SELECT id FROM Profile WHERE is_current)
         "#
     };
 }
@@ -292,44 +453,60 @@ impl ResultPack {
 }
 
 impl<'a> ViewPack<'a> {
-    pub fn execute(&self, conn: &duckdb::Connection) -> anyhow::Result<ResultPack> {
+    pub fn execute(&self, conn: &duckdb::Connection) -> Result<ResultPack> {
         let sql = self.to_sql();
         let mut stmt = conn.prepare(&sql)?;
+        let params = params_from_iter(self.params());
 
         // 根据变体选择解析函数
         match self {
             Self::GetPackInfo => {
-                let result = PackInfo::get_res(&mut stmt)?;
+                let result = PackInfo::get_res(&mut stmt, params)?;
                 Ok(ResultPack::InfoList(result))
             }
             Self::GetFileById(_) => {
-                let result = FileTreeNode::get_res(&mut stmt)?;
+                let result = FileTreeNode::get_res(&mut stmt, params)?;
                 Ok(ResultPack::FileList(result))
             }
         }
     }
 
+    /// 生成带占位符的 SQL，参数由 [`Self::params`] 提供
     #[inline]
     pub fn to_sql(&self) -> Cow<'static, str> {
         use ViewPack::*;
         match self {
             GetPackInfo => (Self::SQL_GET_LIST.to_string() + "order by priority asc").into(),
-            GetFileById(id) => {
-                let sql = format!(
-                    "{} WHERE f.pack_id = '{}' ORDER BY f.path_segments;",
-                    Self::SQL_GET_,
-                    id
-                );
-                sql.into()
-            }
+            GetFileById(_) => format!(
+                "{} WHERE f.pack_id = ? ORDER BY f.path_segments;",
+                Self::SQL_GET_
+            )
+            .into(),
+        }
+    }
+
+    /// 与 [`Self::to_sql`] 中占位符对应的参数
+    pub fn params(&self) -> Vec<Value> {
+        use ViewPack::*;
+        match self {
+            GetPackInfo => vec![],
+            GetFileById(id) => vec![Value::Text(id.to_string())],
         }
     }
 }
 
 /// 包的管理
+///
+/// 包的优先级、启用与部署状态记录在当前方案中，插入的包在所有方案中默认不启用
 pub enum ListPack<'a> {
     /// 插入包 参数为包的路径、基础信息
     Insert(&'a str, InfoBase),
+    /// 以压缩包为来源插入包，不解压，部署时只解压用到的文件，参数为压缩包路径、基础信息
+    InsertArchive(&'a str, InfoBase),
+    /// 将压缩包解压到存储目录下以包id命名的子目录后插入包，参数为压缩包路径、存储目录、基础信息
+    ImportArchive(&'a str, &'a str, InfoBase),
+    /// 以任意来源插入包，参数为来源、基础信息
+    InsertSource(Arc<dyn PackSource>, InfoBase),
     /// 删除包 参数为包的id
     RemoveById(&'a str),
     /// 更新包的活动状态，参数为文件id、是否活动
@@ -342,63 +519,159 @@ pub enum ListPack<'a> {
     SetInfo(&'a str, InfoBase),
     /// 整理优先级，去除因为插入导致的小数，但不重置计数器
     ResetPriority,
+    /// 重新扫描包的目录，增量更新文件节点，参数为包的id
+    Rescan(&'a str),
 }
 
 impl<'a> ListPack<'a> {
     const SQL_REMOVE_PACK: &'static str = include_str!(r"..\sql\tool\delete_pack.sql");
     const SQL_TOOL_RESET_PRIORITY: &'static str = include_str!(r"..\sql\tool\reset_priority.sql");
 
-    fn set_pack_sql(key: &str, value: impl Display, id: &str) -> String {
+    fn set_pack_sql(key: &str) -> String {
         format!(
-            "UPDATE PriorityPack SET {} = {} WHERE pack_id = '{}';",
-            key, value, id
+            "UPDATE PriorityPack SET {} = ? WHERE pack_id = ? AND profile_id = {};",
+            key, SQL_CURRENT_PROFILE
         )
     }
     /// 执行包相关操作
-    pub fn execute(&self, conn: &Connection) {
+    ///
+    /// 插入、删除、更新信息与重新扫描涉及多条语句，在单个事务中执行
+    pub fn execute(&self, conn: &Connection) -> Result<()> {
+        self.execute_with(conn, &ScanOptions::default())
+    }
+
+    /// 执行包相关操作，插入与重新扫描时报告进度并响应取消
+    pub fn execute_with(&self, conn: &Connection, options: &ScanOptions) -> Result<()> {
+        use ListPack::*;
+        match self {
+            Insert(..) | InsertArchive(..) | ImportArchive(..) | InsertSource(..)
+            | RemoveById(_) | SetInfo(..) | Rescan(_) => {
+                transaction(conn, |conn| self.execute_in(conn, options))
+            }
+            _ => self.execute_in(conn, options),
+        }
+    }
+
+    /// 取出高于当前方案中所有包的优先级
+    ///
+    /// 当前最大优先级加一，新插入的包同样按各方案的最大优先级排在最后，不会取到相同的值
+    pub(crate) fn next_priority(conn: &Connection) -> Result<f64> {
+        let next = conn.query_row(
+            &format!(
+                "SELECT COALESCE(max(priority), 0) + 1 FROM PriorityPack WHERE profile_id = {};",
+                SQL_CURRENT_PROFILE
+            ),
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(next)
+    }
+
+    /// 遍历包并保存文件节点与基础信息
+    fn insert_pack(
+        conn: &Connection,
+        mut pack: FNPack,
+        info: &InfoBase,
+        options: &ScanOptions,
+    ) -> Result<()> {
+        let options = options.clone().with_base_ignore(IgnoreRules::global(conn)?);
+        pack.walk_dir_with(&options)?;
+        pack.save_all_files_to_db(conn)?;
+        conn.execute(&info.to_sql(), params_from_iter(info.params()))?;
+        Ok(())
+    }
+
+    /// 在调用方已开启的事务中执行
+    pub(crate) fn execute_in(&self, conn: &Connection, options: &ScanOptions) -> Result<()> {
         use ListPack::*;
         match self {
             Insert(path, info) => {
-                // 创建并保存第一个包
-                let mut root = FNPack::new(path, info.id);
-                root.walk_dir();
-                root.save_all_files_to_db(conn);
-                conn.execute(&info.to_sql(), []).unwrap();
+                if !std::path::Path::new(path).is_dir() {
+                    return Err(Error::NotFound(format!("包目录不存在: {}", path)));
+                }
+                Self::insert_pack(conn, FNPack::new(path, info.id), info, options)?;
+            }
+            InsertArchive(path, info) => {
+                let pack = FNPack::from_source(Arc::new(Archive::open(path)?), info.id);
+                Self::insert_pack(conn, pack, info, options)?;
+            }
+            ImportArchive(path, storage, info) => {
+                let archive = Archive::open(*path)?;
+                let dir = std::path::Path::new(storage).join(info.id.to_string());
+                if dir.exists() {
+                    return Err(Error::InvalidInput(format!(
+                        "存储目录已存在: {}",
+                        dir.display()
+                    )));
+                }
+                // 解压或入库失败时删除已解压的文件，事务回滚不会恢复磁盘
+                let res = archive.extract_all(&dir).and_then(|_| {
+                    let pack = FNPack::new(&dir.to_string_lossy(), info.id);
+                    Self::insert_pack(conn, pack, info, options)
+                });
+                if res.is_err() {
+                    if let Err(e) = std::fs::remove_dir_all(&dir) {
+                        log::error!("清理解压目录失败 {}: {}", dir.display(), e);
+                    }
+                }
+                res?;
+            }
+            InsertSource(source, info) => {
+                let pack = FNPack::from_source(source.clone(), info.id);
+                Self::insert_pack(conn, pack, info, options)?;
             }
             RemoveById(id) => {
-                let sql = Self::SQL_REMOVE_PACK.replace('?', &format!("'{}'", id));
-                conn.execute_batch(&sql).unwrap();
+                // 脚本中每条语句都以包id为唯一参数
+                for sql in Self::SQL_REMOVE_PACK
+                    .split(';')
+                    .filter(|sql| !sql.trim().is_empty())
+                {
+                    conn.execute(sql, [*id])?;
+                }
             }
             SetActive(file_id, is_active) => {
-                let sql = Self::set_pack_sql("is_active", is_active, file_id);
-                conn.execute(&sql, []).unwrap();
+                let sql = Self::set_pack_sql("is_active");
+                conn.execute(&sql, params![is_active, file_id])?;
             }
             SetDeployed(file_id, is_deployed) => {
-                let sql = Self::set_pack_sql("is_deployed", is_deployed, file_id);
-                conn.execute(&sql, []).unwrap();
+                let sql = Self::set_pack_sql("is_deployed");
+                conn.execute(&sql, params![is_deployed, file_id])?;
             }
             SetPriority(file_id, priority) => {
-                let sql = Self::set_pack_sql("priority", priority, file_id);
-                conn.execute(&sql, []).unwrap();
+                let sql = Self::set_pack_sql("priority");
+                conn.execute(&sql, params![priority, file_id])?;
             }
             SetInfo(file_id, info) => {
                 // 删除包的信息
-                let sql = format!("DELETE FROM InfoBase WHERE id = '{}';", file_id);
-                conn.execute(&sql, []).unwrap();
+                conn.execute("DELETE FROM InfoBase WHERE id = ?;", [*file_id])?;
                 // 插入新的信息
-                let sql = info.to_sql();
-                conn.execute_batch(&sql).unwrap();
+                conn.execute(&info.to_sql(), params_from_iter(info.params()))?;
             }
             ResetPriority => {
-                conn.execute_batch(&Self::SQL_TOOL_RESET_PRIORITY).unwrap();
+                conn.execute_batch(&Self::SQL_TOOL_RESET_PRIORITY)?;
+            }
+            Rescan(id) => {
+                // 不是合法 UUID 的包id不可能存在
+                let uuid = id
+                    .parse()
+                    .map_err(|_| Error::NotFound(format!("包: {}", id)))?;
+                let options = options.clone().with_base_ignore(IgnoreRules::global(conn)?);
+                let mut pack = FNPack::from_source(pack_source(conn, id)?, uuid);
+                pack.rescan_with(conn, &options)?;
             }
         }
+        Ok(())
     }
 }
 
 /// 文件管理
+///
+/// 文件启用状态、独立覆盖规则、模式覆盖规则与排除规则记录在当前方案中，
+/// 规则id只能操作当前方案的规则
 pub enum ListTree<'a> {
     /// 更新文件的活动状态，参数为文件id、是否活动
+    ///
+    /// 停用的文件不再参与覆盖，该路径由下一个提供它的包接管，没有其他包提供时不部署
     SetActive(&'a str, bool),
     /// 设置部署状态
     SetDeployed(&'a str, bool),
@@ -408,43 +681,142 @@ pub enum ListTree<'a> {
     RemoveCoverRule(&'a str),
     /// 按包id移除独立覆盖规则
     RemoveCoverRuleByPackId(&'a str),
+    /// 添加模式覆盖规则，匹配的路径取自指定包，参数为规则类型、路径、包id
+    ///
+    /// 后添加的规则优先，独立覆盖规则优先于所有模式覆盖规则
+    AddPatternRule(PatternKind, &'a str, &'a str),
+    /// 移除模式覆盖规则，参数为规则id
+    RemovePatternRule(i64),
+    /// 按包id移除模式覆盖规则
+    RemovePatternRuleByPackId(&'a str),
+    /// 更新模式覆盖规则的顺序，参数为规则id、顺序，顺序大的规则优先
+    SetPatternRuleOrder(i64, f64),
+    /// 更新模式覆盖规则的启用状态，参数为规则id、是否启用
+    SetPatternRuleActive(i64, bool),
+    /// 添加排除规则，匹配的路径不论由哪些包提供都从覆盖树中移除，参数为规则类型、路径
+    AddExcludeRule(PatternKind, &'a str),
+    /// 移除排除规则，参数为规则id
+    RemoveExcludeRule(i64),
+    /// 更新排除规则的启用状态，参数为规则id、是否启用
+    SetExcludeRuleActive(i64, bool),
 }
 
 impl ListTree<'_> {
-    fn set_file_node_sql(key: &str, value: impl Display, id: &str) -> String {
-        format!(
-            "UPDATE FileNode SET {} = {} WHERE id = '{}';",
-            key, value, id
-        )
+    fn set_file_node_sql(key: &str) -> String {
+        format!("UPDATE FileNode SET {} = ? WHERE id = ?;", key)
     }
 
     /// 生成 SQL 或执行操作
-    pub fn execute(&self, conn: &Connection) {
+    pub fn execute(&self, conn: &Connection) -> Result<()> {
         use ListTree::*;
         match self {
             SetActive(file_id, is_active) => {
-                let sql = Self::set_file_node_sql("is_active", is_active, file_id);
-                conn.execute(&sql, []).unwrap();
+                let sql = format!(
+                    "INSERT OR REPLACE INTO ProfileFile (profile_id, file_id, is_active) VALUES ({}, ?, ?);",
+                    SQL_CURRENT_PROFILE
+                );
+                conn.execute(&sql, params![file_id, is_active])?;
             }
             SetDeployed(file_id, is_deployed) => {
-                let sql = Self::set_file_node_sql("is_deployed", is_deployed, file_id);
-                conn.execute(&sql, []).unwrap();
+                let sql = Self::set_file_node_sql("is_deployed");
+                conn.execute(&sql, params![is_deployed, file_id])?;
             }
             AddCoverRule(path, file_id, pack_id) => {
                 let sql = format!(
-                    "insert into PriorityFN (path, main_id, pack_id) values ('{}', '{}', '{}');",
-                    path, file_id, pack_id
+                    "insert into PriorityFN (profile_id, path, main_id, pack_id) values ({}, ?, ?, ?);",
+                    SQL_CURRENT_PROFILE
                 );
-                conn.execute(&sql, []).unwrap();
+                conn.execute(&sql, params![path, file_id, pack_id])?;
             }
-            RemoveCoverRule(file_id) => {
-                let sql = format!("DELETE FROM PriorityFN WHERE path = '{}';", file_id);
-                conn.execute(&sql, []).unwrap();
+            RemoveCoverRule(path) => {
+                let sql = format!(
+                    "DELETE FROM PriorityFN WHERE path = ? AND profile_id = {};",
+                    SQL_CURRENT_PROFILE
+                );
+                conn.execute(&sql, [*path])?;
             }
             RemoveCoverRuleByPackId(pack_id) => {
-                let sql = format!("DELETE FROM PriorityFN WHERE pack_id = '{}';", pack_id);
-                conn.execute(&sql, []).unwrap();
+                let sql = format!(
+                    "DELETE FROM PriorityFN WHERE pack_id = ? AND profile_id = {};",
+                    SQL_CURRENT_PROFILE
+                );
+                conn.execute(&sql, [*pack_id])?;
+            }
+            AddPatternRule(kind, pattern, pack_id) => {
+                let sql = format!(
+                    "INSERT INTO PriorityRule (profile_id, kind, pattern, regex, pack_id) VALUES ({}, ?, ?, ?, ?);",
+                    SQL_CURRENT_PROFILE
+                );
+                conn.execute(
+                    &sql,
+                    params![kind.as_str(), pattern, kind.to_regex(pattern)?, pack_id],
+                )?;
+            }
+            RemovePatternRule(id) => {
+                let sql = format!(
+                    "DELETE FROM PriorityRule WHERE id = ? AND profile_id = {};",
+                    SQL_CURRENT_PROFILE
+                );
+                conn.execute(&sql, [id])?;
+            }
+            RemovePatternRuleByPackId(pack_id) => {
+                let sql = format!(
+                    "DELETE FROM PriorityRule WHERE pack_id = ? AND profile_id = {};",
+                    SQL_CURRENT_PROFILE
+                );
+                conn.execute(&sql, [*pack_id])?;
+            }
+            SetPatternRuleOrder(id, order) => {
+                let sql = format!(
+                    "UPDATE PriorityRule SET rule_order = ? WHERE id = ? AND profile_id = {};",
+                    SQL_CURRENT_PROFILE
+                );
+                conn.execute(&sql, params![order, id])?;
+            }
+            SetPatternRuleActive(id, is_active) => {
+                let sql = format!(
+                    "UPDATE PriorityRule SET is_active = ? WHERE id = ? AND profile_id = {};",
+                    SQL_CURRENT_PROFILE
+                );
+                conn.execute(&sql, params![is_active, id])?;
+            }
+            AddExcludeRule(kind, pattern) => {
+                let sql = format!(
+                    "INSERT INTO ExcludeRule (profile_id, kind, pattern, regex) VALUES ({}, ?, ?, ?);",
+                    SQL_CURRENT_PROFILE
+                );
+                conn.execute(
+                    &sql,
+                    params![kind.as_str(), pattern, kind.to_regex(pattern)?],
+                )?;
+            }
+            RemoveExcludeRule(id) => {
+                let sql = format!(
+                    "DELETE FROM ExcludeRule WHERE id = ? AND profile_id = {};",
+                    SQL_CURRENT_PROFILE
+                );
+                conn.execute(&sql, [id])?;
+            }
+            SetExcludeRuleActive(id, is_active) => {
+                let sql = format!(
+                    "UPDATE ExcludeRule SET is_active = ? WHERE id = ? AND profile_id = {};",
+                    SQL_CURRENT_PROFILE
+                );
+                conn.execute(&sql, params![is_active, id])?;
             }
         }
+        Ok(())
+    }
+
+    /// 获取当前方案的所有模式覆盖规则，按生效顺序排列
+    pub fn pattern_rules(conn: &Connection) -> Result<Vec<PatternRule>> {
+        let mut stmt = conn.prepare(PatternRule::SQL_GET_ALL)?;
+        PatternRule::get_res(&mut stmt, [])
+    }
+
+    /// 获取当前方案的所有排除规则
+    pub fn exclude_rules(conn: &Connection) -> Result<Vec<ExcludeRule>> {
+        let mut stmt = conn.prepare(ExcludeRule::SQL_GET_ALL)?;
+        ExcludeRule::get_res(&mut stmt, [])
     }
 }
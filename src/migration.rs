@@ -0,0 +1,155 @@
+use duckdb::{Connection, OptionalExt};
+
+use crate::{Error, Result, transaction};
+
+const SQL_INIT: &'static str = include_str!(r"..\sql\init\core.sql");
+const SQL_INIT_INFO: &'static str = include_str!(r"..\sql\init\info.sql");
+const SQL_INIT_DEPLOY: &'static str = include_str!(r"..\sql\init\deploy.sql");
+const SQL_FILE_META: &'static str = include_str!(r"..\sql\migration\0002_file_meta.sql");
+const SQL_IGNORE_RULE: &'static str = include_str!(r"..\sql\migration\0003_ignore_rule.sql");
+const SQL_ARCHIVE_PACK: &'static str = include_str!(r"..\sql\migration\0004_archive_pack.sql");
+const SQL_PATTERN_RULE: &'static str = include_str!(r"..\sql\migration\0005_pattern_rule.sql");
+const SQL_EXCLUDE_RULE: &'static str = include_str!(r"..\sql\migration\0006_exclude_rule.sql");
+const SQL_PROFILE: &'static str = include_str!(r"..\sql\migration\0007_profile.sql");
+const SQL_PACK_RELATION: &'static str = include_str!(r"..\sql\migration\0008_pack_relation.sql");
+const SQL_DEPLOYED_FILE_META: &'static str =
+    include_str!(r"..\sql\migration\0009_deployed_file_meta.sql");
+const SQL_PACK_SOURCE_KIND: &'static str =
+    include_str!(r"..\sql\migration\0010_pack_source_kind.sql");
+const SQL_DROP_FILE_NODE_STAGING: &'static str =
+    include_str!(r"..\sql\migration\0011_drop_file_node_staging.sql");
+
+/// 版本记录表
+const SQL_CREATE_VERSION: &'static str = r#"
+    CREATE TABLE IF NOT EXISTS SchemaVersion (
+        version INTEGER PRIMARY KEY,
+        applied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+    );
+    "#;
+
+/// 单个迁移
+pub struct Migration {
+    /// 迁移后的版本号
+    pub version: i32,
+    /// 说明
+    pub description: &'static str,
+    /// 依次执行的 SQL
+    pub sql: &'static [&'static str],
+}
+
+/// 按版本号排列的迁移列表
+///
+/// 版本 1 为初始表结构，之后的表结构变更只能追加新的迁移，不能修改已发布的迁移
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "初始表结构",
+        sql: &[SQL_INIT, SQL_INIT_INFO, SQL_INIT_DEPLOY],
+    },
+    Migration {
+        version: 2,
+        description: "FileNode 增加修改时间与 Unix 元数据",
+        sql: &[SQL_FILE_META],
+    },
+    Migration {
+        version: 3,
+        description: "全局忽略规则",
+        sql: &[SQL_IGNORE_RULE],
+    },
+    Migration {
+        version: 4,
+        description: "压缩包来源的包",
+        sql: &[SQL_ARCHIVE_PACK],
+    },
+    Migration {
+        version: 5,
+        description: "模式覆盖规则",
+        sql: &[SQL_PATTERN_RULE],
+    },
+    Migration {
+        version: 6,
+        description: "排除规则",
+        sql: &[SQL_EXCLUDE_RULE],
+    },
+    Migration {
+        version: 7,
+        description: "配置方案",
+        sql: &[SQL_PROFILE],
+    },
+    Migration {
+        version: 8,
+        description: "包关系",
+        sql: &[SQL_PACK_RELATION],
+    },
+    Migration {
+        version: 9,
+        description: "部署记录增加文件大小与修改时间",
+        sql: &[SQL_DEPLOYED_FILE_META],
+    },
+    Migration {
+        version: 10,
+        description: "包来源类型",
+        sql: &[SQL_PACK_SOURCE_KIND],
+    },
+    Migration {
+        version: 11,
+        description: "文件节点中转表改为临时表",
+        sql: &[SQL_DROP_FILE_NODE_STAGING],
+    },
+];
+
+/// 当前库支持的表结构版本
+pub const SCHEMA_VERSION: i32 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+/// 读取数据库的表结构版本，未记录时为 0
+pub fn schema_version(conn: &Connection) -> Result<i32> {
+    let exists = conn
+        .query_row(
+            "SELECT 1 FROM information_schema.tables WHERE table_name = 'SchemaVersion';",
+            [],
+            |row| row.get::<_, i32>(0),
+        )
+        .optional()?
+        .is_some();
+    if !exists {
+        return Ok(0);
+    }
+    let version: Option<i32> =
+        conn.query_row("SELECT max(version) FROM SchemaVersion;", [], |row| {
+            row.get(0)
+        })?;
+    Ok(version.unwrap_or(0))
+}
+
+/// 依次执行未应用的迁移，返回迁移后的版本
+///
+/// 数据库版本高于本库支持的版本时拒绝打开
+pub fn migrate(conn: &Connection) -> Result<i32> {
+    let current = schema_version(conn)?;
+    if current > SCHEMA_VERSION {
+        return Err(Error::SchemaTooNew {
+            found: current,
+            supported: SCHEMA_VERSION,
+        });
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        log::info!(
+            "数据库迁移到版本 {}: {}",
+            migration.version,
+            migration.description
+        );
+        transaction(conn, |conn| {
+            conn.execute_batch(SQL_CREATE_VERSION)?;
+            for sql in migration.sql {
+                conn.execute_batch(sql)?;
+            }
+            conn.execute(
+                "INSERT INTO SchemaVersion (version) VALUES (?);",
+                [migration.version],
+            )?;
+            Ok(())
+        })?;
+    }
+    Ok(SCHEMA_VERSION)
+}
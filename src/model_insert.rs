@@ -1,11 +1,104 @@
-use std::sync::Arc;
-
-use hashbrown::HashMap;
-use parking_lot::Mutex;
-use rayon::iter::{ParallelBridge, ParallelIterator};
+use std::{
+    io::Read,
+    path::Path,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use duckdb::{Connection, params, types::Value};
+use hashbrown::{HashMap, HashSet};
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use walkdir::WalkDir;
+
+use crate::{
+    Archive, ArchiveKind, DirSource, KnownFile, PackSource, Result, ScanOptions, ScanState,
+    list_placeholders,
+};
+
+/// 计算文件的 blake3 哈希
+pub fn hash_file(path: &Path, size: u64) -> std::io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    if size >= 16 * 1024 {
+        // 对于大文件，使用内存映射
+        hasher.update_mmap(path)?;
+    } else {
+        // 对于小文件，使用流式读取
+        let mut file = std::fs::File::open(path)?;
+        hasher.update_reader(&mut file)?;
+    }
+    let result = hasher.finalize();
+    Ok(result.to_hex().to_string())
+}
+
+/// 分块读取的大小，超过此大小的文件分块计算哈希
+const HASH_CHUNK: usize = 1024 * 1024;
+
+/// 计算文件哈希并报告进度，大文件分块读取，每块之间检查是否已取消
+pub(crate) fn hash_file_with(path: &Path, size: u64, state: &ScanState) -> Result<String> {
+    if size < HASH_CHUNK as u64 {
+        let hash = hash_file(path, size)?;
+        state.hashed(path, size);
+        return Ok(hash);
+    }
+
+    hash_reader(std::fs::File::open(path)?, path, state)
+}
+
+/// 分块读取并计算哈希，每块之间检查是否已取消，path 只用于报告进度
+pub(crate) fn hash_reader(mut reader: impl Read, path: &Path, state: &ScanState) -> Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    let mut buf = vec![0; HASH_CHUNK];
+    loop {
+        state.check()?;
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        hasher.update(&buf[..n]);
+        state.hashed(path, n as u64);
+    }
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// 时间点的毫秒时间戳
+pub(crate) fn millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Unix 文件元数据，用于检测文件替换与硬链接
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnixMeta {
+    /// 设备号
+    pub dev: u64,
+    /// inode
+    pub inode: u64,
+    /// 权限模式
+    pub mode: u32,
+}
+
+impl UnixMeta {
+    /// 从文件元数据中读取，非 Unix 平台返回 None
+    #[cfg(unix)]
+    pub fn from_metadata(metadata: &std::fs::Metadata) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+        Some(Self {
+            dev: metadata.dev(),
+            inode: metadata.ino(),
+            mode: metadata.mode(),
+        })
+    }
+
+    /// 从文件元数据中读取，非 Unix 平台返回 None
+    #[cfg(not(unix))]
+    pub fn from_metadata(_metadata: &std::fs::Metadata) -> Option<Self> {
+        None
+    }
+}
 
 /// 文件节点
 pub struct FileNode {
@@ -25,6 +118,10 @@ pub struct FileNode {
     hash: Option<String>,
     /// 大小
     size: Option<u64>,
+    /// 修改时间（毫秒时间戳）
+    mtime: Option<i64>,
+    /// Unix 文件元数据
+    unix: Option<UnixMeta>,
 }
 
 impl FileNode {
@@ -38,6 +135,8 @@ impl FileNode {
         is_dir: bool,
         hash: Option<String>,
         size: Option<u64>,
+        mtime: Option<i64>,
+        unix: Option<UnixMeta>,
     ) -> Self {
         Self {
             id,
@@ -48,40 +147,114 @@ impl FileNode {
             is_dir,
             hash,
             size,
+            mtime,
+            unix,
         }
     }
 
-    /// 生成插入 FileNode 的 SQL 语句
+    /// 生成插入 FileNode 的 SQL 语句，参数由 [`Self::params`] 提供
     pub fn to_sql(&self) -> String {
-        let array_str = self
-            .path_segments
-            .iter()
-            .map(|s| format!("'{}'", s))
-            .collect::<Vec<String>>()
-            .join(", ");
-
-        let parent_id_str = match self.parent_id {
-            Some(pid) => format!("'{}'", pid),
-            None => "NULL".to_string(),
-        };
-
-        let hash_str = match &self.hash {
-            Some(h) => format!("'{}'", h),
-            None => "NULL".to_string(),
-        };
-
-        let size_str = match self.size {
-            Some(s) => s.to_string(),
-            None => "NULL".to_string(),
-        };
+        format!(
+            "INSERT INTO FileNode (id, pack_id, parent_id, name, path_segments, is_dir, size, hash, mtime, dev, inode, mode)
+            VALUES (?, ?, ?, ?, {}, ?, ?, ?, ?, ?, ?, ?);",
+            list_placeholders(self.path_segments.len())
+        )
+    }
 
-        let sql = format!(
-            "INSERT INTO FileNode (id, pack_id, parent_id, name, path_segments, is_dir,  size, hash) 
-            VALUES ('{}', '{}', {}, '{}', ARRAY[{}], {}, {}, {});",
-            self.id, self.pack_id, parent_id_str, self.name, array_str, self.is_dir, size_str, hash_str
+    /// 批量写入的中转表，duckdb 的 Appender 不支持列表列，path_segments 以 '/' 拼接后写入
+    ///
+    /// 临时表只对当前连接可见，每次写入前重新创建，克隆出的连接之间互不影响，
+    /// 也不会导入之前失败的写入留下的数据
+    const SQL_CREATE_STAGING: &'static str = r#"
+        CREATE OR REPLACE TEMP TABLE FileNodeStaging (
+            id VARCHAR NOT NULL,
+            pack_id VARCHAR NOT NULL,
+            parent_id VARCHAR,
+            name VARCHAR NOT NULL,
+            path VARCHAR NOT NULL,
+            is_dir BOOLEAN NOT NULL,
+            size UBIGINT,
+            hash VARCHAR,
+            mtime BIGINT,
+            dev UBIGINT,
+            inode UBIGINT,
+            mode UINTEGER
         );
+        "#;
+
+    /// 将中转表整体转存到 FileNode
+    const SQL_MERGE_STAGING: &'static str = r#"
+        INSERT INTO FileNode (id, pack_id, parent_id, name, path_segments, is_dir, size, hash, mtime, dev, inode, mode)
+        SELECT id::UUID, pack_id::UUID, parent_id::UUID, name, string_split(path, '/'), is_dir, size, hash, mtime, dev, inode, mode
+        FROM FileNodeStaging;
+        "#;
+
+    /// 通过 Appender 批量写入文件节点，返回写入的数量
+    pub fn append_all<'a>(
+        conn: &Connection,
+        nodes: impl IntoIterator<Item = &'a FileNode>,
+    ) -> Result<usize> {
+        conn.execute_batch(Self::SQL_CREATE_STAGING)?;
+        let res = Self::append_staging(conn, nodes);
+        if let Err(e) = conn.execute_batch("DROP TABLE IF EXISTS temp.FileNodeStaging;") {
+            log::warn!("删除文件节点中转表失败: {}", e);
+        }
+        res
+    }
 
-        sql
+    /// 写入中转表后转存到 FileNode
+    fn append_staging<'a>(
+        conn: &Connection,
+        nodes: impl IntoIterator<Item = &'a FileNode>,
+    ) -> Result<usize> {
+        let mut count = 0;
+        {
+            let mut appender = conn.appender("FileNodeStaging")?;
+            for node in nodes {
+                appender.append_row(params![
+                    node.id.to_string(),
+                    node.pack_id.to_string(),
+                    node.parent_id.map(|id| id.to_string()),
+                    node.name,
+                    node.path_segments.join("/"),
+                    node.is_dir,
+                    node.size,
+                    node.hash,
+                    node.mtime,
+                    node.unix.map(|m| m.dev),
+                    node.unix.map(|m| m.inode),
+                    node.unix.map(|m| m.mode),
+                ])?;
+                count += 1;
+            }
+            appender.flush()?;
+        }
+        if count > 0 {
+            conn.execute_batch(Self::SQL_MERGE_STAGING)?;
+        }
+        Ok(count)
+    }
+
+    /// 与 [`Self::to_sql`] 中占位符对应的参数
+    pub fn params(&self) -> Vec<Value> {
+        let mut params = vec![
+            Value::Text(self.id.to_string()),
+            Value::Text(self.pack_id.to_string()),
+            self.parent_id
+                .map_or(Value::Null, |pid| Value::Text(pid.to_string())),
+            Value::Text(self.name.clone()),
+        ];
+        params.extend(self.path_segments.iter().cloned().map(Value::Text));
+        params.extend([
+            Value::Boolean(self.is_dir),
+            self.size.map_or(Value::Null, Value::UBigInt),
+            self.hash.clone().map_or(Value::Null, Value::Text),
+            self.mtime.map_or(Value::Null, Value::BigInt),
+            self.unix.map_or(Value::Null, |m| Value::UBigInt(m.dev)),
+            self.unix.map_or(Value::Null, |m| Value::UBigInt(m.inode)),
+            self.unix.map_or(Value::Null, |m| Value::UInt(m.mode)),
+        ]);
+        params
     }
 }
 
@@ -89,18 +262,28 @@ impl FileNode {
 pub struct FNPack {
     /// 唯一id
     id: Uuid,
-    /// 存放路径
-    path: String,
+    /// 文件来源
+    source: Arc<dyn PackSource>,
     /// 字节点表
     children: Option<Vec<FileNode>>,
 }
 
 impl FNPack {
-    /// 创建新的文件包
+    /// 创建以本地目录为来源的文件包
     pub fn new(path: &str, id: Uuid) -> Self {
+        Self::from_source(Arc::new(DirSource::new(path)), id)
+    }
+
+    /// 创建以压缩包为来源的文件包，文件节点直接从压缩包中读取
+    pub fn from_archive(path: &str, id: Uuid, kind: ArchiveKind) -> Self {
+        Self::from_source(Arc::new(Archive::new(path, kind)), id)
+    }
+
+    /// 创建以任意来源构造的文件包
+    pub fn from_source(source: Arc<dyn PackSource>, id: Uuid) -> Self {
         Self {
             id,
-            path: path.to_string(),
+            source,
             children: None,
         }
     }
@@ -110,131 +293,202 @@ impl FNPack {
         self.id
     }
 
-    /// 遍历目录，生成文件节点
-    pub fn walk_dir(&mut self) {
-        let pack_id = self.id;
-        let path = &self.path;
-
-        let id_map = Arc::new(Mutex::new(HashMap::new())); // path -> id
-        let res = WalkDir::new(path)
-            .follow_links(false)
-            .min_depth(1)
-            .into_iter()
-            .par_bridge()
-            .filter_map(|e| e.ok())
-            .map(|entry| {
-                let id = Uuid::new_v4();
-                let path = entry.path().to_path_buf();
-                let name = entry.file_name().to_string_lossy().to_string();
-                let is_dir = entry.file_type().is_dir();
-
-                let size = match is_dir {
-                    false => Some(entry.metadata().unwrap().len()),
-                    _ => None,
-                };
-
-                let hash = match is_dir {
-                    false => {
-                        let mut hasher = blake3::Hasher::new();
-                        if let Some(size) = size {
-                            if size >= 16 * 1024 {
-                                // 对于大文件，使用内存映射
-                                hasher.update_mmap(&path).unwrap();
-                            } else {
-                                // 对于小文件，使用流式读取
-                                let mut file = std::fs::File::open(&path).unwrap();
-                                hasher.update_reader(&mut file).unwrap();
-                            }
-                        }
-                        let result = hasher.finalize();
-                        Some(result.to_hex().to_string())
-                    }
-                    _ => None,
-                };
+    /// 包的文件来源
+    pub fn source(&self) -> &Arc<dyn PackSource> {
+        &self.source
+    }
 
-                {
-                    let mut map = id_map.lock();
-                    map.insert(path.clone(), id);
-                }
+    /// 遍历得到的文件节点，未遍历时为空
+    pub fn children(&self) -> &[FileNode] {
+        self.children.as_deref().unwrap_or_default()
+    }
+
+    /// 遍历来源，生成文件节点
+    pub fn walk_dir(&mut self) -> Result<()> {
+        self.walk_dir_with(&ScanOptions::default())
+    }
+
+    /// 遍历来源，生成文件节点，遍历中报告进度并响应取消
+    pub fn walk_dir_with(&mut self, options: &ScanOptions) -> Result<()> {
+        self.walk_dir_known(&HashMap::new(), options)
+    }
 
-                if let Some(parent_path) = path.parent() {
-                    let pid = {
-                        let map = id_map.lock();
-                        map.get(parent_path).cloned().unwrap_or(pack_id)
-                    };
-
-                    let path_segments = {
-                        let curr_path = path.to_str().unwrap();
-                        let root_path = self.path.clone();
-                        let relative_path = curr_path.strip_prefix(&root_path).unwrap();
-                        let segments: Vec<&str> = relative_path
-                            .split(|c| c == '/' || c == '\\')
-                            .filter(|s| !s.is_empty()) // 过滤掉空字符串
-                            .collect();
-                        segments
-                            .iter()
-                            .map(|&s| s.to_string())
-                            .collect::<Vec<String>>()
-                    };
-
-                    Some(FileNode::new(
-                        id,
-                        pack_id,
-                        Some(pid),
-                        name,
-                        path_segments,
-                        is_dir,
-                        hash,
-                        size,
-                    ))
-                } else {
-                    None
+    /// 遍历来源，生成文件节点
+    ///
+    /// known 为已入库的文件（按相对路径切片索引），路径与类型相同的沿用原有id，
+    /// 大小与修改时间都未变化的文件沿用原有哈希而不重新计算，
+    /// 取消时返回 [`crate::Error::Cancelled`] 且不修改已有的文件节点
+    pub fn walk_dir_known(
+        &mut self,
+        known: &HashMap<Vec<String>, KnownFile>,
+        options: &ScanOptions,
+    ) -> Result<()> {
+        let pack_id = self.id;
+        let source = self.source.as_ref();
+        let state = ScanState::new(options);
+        // 全局规则在前，来源根目录的 .vfsignore 优先
+        let mut ignore = options.ignore().clone();
+        ignore.extend(source.ignore_rules()?);
+
+        let mut entries = source.entries(&ignore, &state)?;
+        entries.par_iter_mut().try_for_each(|entry| -> Result<()> {
+            if entry.is_dir || entry.hash.is_some() {
+                return Ok(());
+            }
+            state.check()?;
+            entry.hash = match known.get(&entry.path_segments) {
+                // 大小、修改时间与 inode 都未变化，沿用原有哈希
+                Some(k)
+                    if !k.is_dir
+                        && k.hash.is_some()
+                        && k.size == entry.size
+                        && k.mtime == entry.mtime
+                        && k.inode == entry.unix.map(|m| m.inode) =>
+                {
+                    k.hash.clone()
                 }
-            })
-            .filter_map(|v| v)
-            .collect::<Vec<_>>();
+                _ => Some(source.hash(entry, &state)?),
+            };
+            Ok(())
+        })?;
+
+        // 上级目录先于其内容处理
+        entries.sort_by(|a, b| a.path_segments.cmp(&b.path_segments));
+        let mut ids: HashMap<Vec<String>, Uuid> = HashMap::new(); // path -> id
+        let mut res = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let Some((name, parent)) = entry.path_segments.split_last() else {
+                continue;
+            };
+            let id = known
+                .get(&entry.path_segments)
+                .filter(|k| k.is_dir == entry.is_dir)
+                .map(|k| k.id)
+                .unwrap_or_else(Uuid::new_v4);
+            let pid = ids.get(parent).copied().unwrap_or(pack_id);
+            let name = name.clone();
+            ids.insert(entry.path_segments.clone(), id);
+
+            res.push(FileNode::new(
+                id,
+                pack_id,
+                Some(pid),
+                name,
+                entry.path_segments,
+                entry.is_dir,
+                entry.hash,
+                entry.size,
+                entry.mtime,
+                entry.unix,
+            ));
+        }
 
         self.children = Some(res);
+        Ok(())
     }
 
-    /// 生成插入 FNPack 的 SQL 语句
-    pub fn to_sql(&self) -> String {
-        let sql = format!(
-            "INSERT INTO FNPack (id, base_path) VALUES ('{}', '{}');",
-            self.id, self.path
-        );
-        // println!("FNPack sql: {}", sql);
-        sql
+    /// 重新扫描已入库的包，增量更新文件节点
+    ///
+    /// 未变化的路径保留原有id及启用状态，新增的路径插入，消失的路径连同指向它的独立覆盖规则一起删除，
+    /// 只有大小或修改时间变化的文件会重新计算哈希
+    pub fn rescan(&mut self, conn: &Connection) -> Result<()> {
+        self.rescan_with(conn, &ScanOptions::default())
     }
 
-    /// 保存所有文件节点到数据库
-    pub fn save_all_files_to_db(&self, conn: &duckdb::Connection) {
-        if self.children.is_none() {
-            return;
+    /// 重新扫描已入库的包，扫描中报告进度并响应取消，取消时不修改数据库
+    pub fn rescan_with(&mut self, conn: &Connection, options: &ScanOptions) -> Result<()> {
+        let known = {
+            let mut stmt = conn.prepare(KnownFile::SQL_GET_BY_PACK)?;
+            KnownFile::get_res(&mut stmt, &self.id.to_string())?
+                .into_iter()
+                .map(|k| (k.path_segments.clone(), k))
+                .collect::<HashMap<_, _>>()
+        };
+        self.walk_dir_known(&known, options)?;
+
+        let mut kept = HashSet::new();
+        let mut added = vec![];
+        for node in self.children() {
+            match known.get(&node.path_segments) {
+                Some(k) if k.id == node.id => {
+                    kept.insert(node.id);
+                    if k.parent_id != node.parent_id
+                        || k.size != node.size
+                        || k.mtime != node.mtime
+                        || k.hash != node.hash
+                        || k.dev != node.unix.map(|m| m.dev)
+                        || k.inode != node.unix.map(|m| m.inode)
+                        || k.mode != node.unix.map(|m| m.mode)
+                    {
+                        conn.execute(
+                            "UPDATE FileNode
+                            SET parent_id = ?, size = ?, mtime = ?, hash = ?, dev = ?, inode = ?, mode = ?
+                            WHERE id = ?;",
+                            params![
+                                node.parent_id.map(|id| id.to_string()),
+                                node.size,
+                                node.mtime,
+                                node.hash,
+                                node.unix.map(|m| m.dev),
+                                node.unix.map(|m| m.inode),
+                                node.unix.map(|m| m.mode),
+                                node.id.to_string()
+                            ],
+                        )?;
+                    }
+                }
+                _ => added.push(node),
+            }
+        }
+        FileNode::append_all(conn, added)?;
+
+        for k in known.values().filter(|k| !kept.contains(&k.id)) {
+            conn.execute(
+                "DELETE FROM PriorityFN WHERE main_id = ?;",
+                [k.id.to_string()],
+            )?;
+            conn.execute(
+                "DELETE FROM ProfileFile WHERE file_id = ?;",
+                [k.id.to_string()],
+            )?;
+            conn.execute("DELETE FROM FileNode WHERE id = ?;", [k.id.to_string()])?;
         }
+        Ok(())
+    }
 
-        let sql = self.to_sql();
-        conn.execute(&sql, []).unwrap();
+    /// 插入 FNPack 的 SQL 语句
+    const SQL_INSERT: &'static str =
+        "INSERT INTO FNPack (id, base_path, source_kind, archive_kind) VALUES (?, ?, ?, ?);";
 
-        self.children
-            .as_ref()
-            .unwrap()
-            .into_iter()
-            .for_each(|file_node| {
-                let sql = file_node.to_sql();
-                conn.execute(&sql, []).unwrap();
-            });
+    /// 保存所有文件节点到数据库，文件节点通过 Appender 批量写入
+    pub fn save_all_files_to_db(&self, conn: &duckdb::Connection) -> Result<()> {
+        let Some(children) = &self.children else {
+            return Ok(());
+        };
 
         conn.execute(
-            format!(
-                "INSERT INTO PriorityPack (pack_id)
-                VALUES ('{}');",
-                self.id
-            )
-            .as_str(),
-            [],
-        )
-        .unwrap();
+            Self::SQL_INSERT,
+            params![
+                self.id.to_string(),
+                self.source.base_path(),
+                self.source.source_kind().as_str(),
+                self.source.archive_kind().map(|kind| kind.as_str())
+            ],
+        )?;
+
+        FileNode::append_all(conn, children)?;
+
+        // 每个方案中都记录新包，排在该方案所有包之后，默认不启用
+        // 不使用 priority_seq，手动设置或重新编号后的优先级可能已经超过计数器
+        conn.execute(
+            "INSERT INTO PriorityPack (profile_id, pack_id, priority)
+            SELECT p.id, ?::UUID, COALESCE(max(pp.priority), 0) + 1
+            FROM Profile p
+            LEFT JOIN PriorityPack pp ON pp.profile_id = p.id
+            GROUP BY p.id;",
+            [self.id.to_string()],
+        )?;
+        Ok(())
     }
 }
 
@@ -256,34 +510,26 @@ pub struct InfoBase {
 }
 
 impl InfoBase {
-    /// 生成插入 InfoBase 的 SQL 语句
+    /// 生成插入 InfoBase 的 SQL 语句，参数由 [`Self::params`] 提供
     pub fn to_sql(&self) -> String {
-        let tag_array = self
-            .tag
-            .iter()
-            .map(|s| format!("'{}'", s))
-            .collect::<Vec<String>>()
-            .join(", ");
-
-        let description_str = self
-            .description
-            .as_ref()
-            .map_or("NULL".to_string(), |s| format!("'{}'", s));
-
-        let author_str = self
-            .author
-            .as_ref()
-            .map_or("NULL".to_string(), |s| format!("'{}'", s));
-
-        let version_str = self
-            .version
-            .as_ref()
-            .map_or("NULL".to_string(), |s| format!("'{}'", s));
-
         format!(
             "INSERT INTO InfoBase (id, name, tags, description, author, version)
-            VALUES ('{}', '{}', ARRAY[{}], {}, {}, {});",
-            self.id, self.name, tag_array, description_str, author_str, version_str
+            VALUES (?, ?, {}, ?, ?, ?);",
+            list_placeholders(self.tag.len())
         )
     }
+
+    /// 与 [`Self::to_sql`] 中占位符对应的参数
+    pub fn params(&self) -> Vec<Value> {
+        let mut params = vec![
+            Value::Text(self.id.to_string()),
+            Value::Text(self.name.clone()),
+        ];
+        params.extend(self.tag.iter().cloned().map(Value::Text));
+        params.extend(
+            [&self.description, &self.author, &self.version]
+                .map(|s| s.clone().map_or(Value::Null, Value::Text)),
+        );
+        params
+    }
 }
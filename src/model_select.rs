@@ -1,5 +1,30 @@
-use duckdb::{Statement, types::Value};
-use serde::{Deserialize, Serialize};
+use duckdb::{
+    Params, Statement,
+    types::{TimeUnit, Type, Value},
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use uuid::Uuid;
+
+use crate::Result;
+
+/// 将列值经由 json 转换为结构体
+fn from_value<T: DeserializeOwned>(idx: usize, value: &Value) -> duckdb::Result<T> {
+    serde_json::to_value(value)
+        .and_then(serde_json::from_value)
+        .map_err(|e| duckdb::Error::FromSqlConversionFailure(idx, Type::Any, Box::new(e)))
+}
+
+/// 将时间戳列转换为毫秒
+fn timestamp_millis(idx: usize, value: Value) -> duckdb::Result<i64> {
+    match value.into_inner_as::<(TimeUnit, i64)>() {
+        Some((unit, t)) => Ok(unit.to_micros(t) / 1000),
+        None => Err(duckdb::Error::InvalidColumnType(
+            idx,
+            "timestamp".to_string(),
+            Type::Timestamp,
+        )),
+    }
+}
 
 /// 包信息结构体
 #[derive(Debug, Serialize)]
@@ -33,12 +58,9 @@ pub struct PackInfo {
 impl PackInfo {
     /// 从数据库连接中获取所有包信息
     #[inline]
-    pub fn get_res(stmt: &mut Statement<'_>) -> anyhow::Result<Vec<Self>> {
-        // 局部引入 duckdb 相关类型
-        use duckdb::types::{TimeUnit, Value};
-
+    pub fn get_res(stmt: &mut Statement<'_>, params: impl Params) -> Result<Vec<Self>> {
         // 查询并映射为 PackInfo 结构体
-        let rows = stmt.query_map([], |row| {
+        let rows = stmt.query_map(params, |row| {
             let id: String = row.get(0)?;
             let base_path: String = row.get(1)?;
             let priority: i32 = row.get(2)?;
@@ -61,10 +83,8 @@ impl PackInfo {
                 .collect();
 
             // 处理时间戳字段为 i64（毫秒）
-            let add_time = add_time.into_inner_as::<(TimeUnit, i64)>().unwrap();
-            let add_time = add_time.0.to_micros(add_time.1) / 1000;
-            let meta_updated_at = meta_updated_at.into_inner_as::<(TimeUnit, i64)>().unwrap();
-            let meta_updated_at = meta_updated_at.0.to_micros(meta_updated_at.1) / 1000;
+            let add_time = timestamp_millis(10, add_time)?;
+            let meta_updated_at = timestamp_millis(11, meta_updated_at)?;
 
             Ok(PackInfo {
                 id,
@@ -107,15 +127,21 @@ pub struct FileTreeNode {
     pub is_deployed: bool,
     /// 在树中的深度
     pub depth: i32,
+    /// 修改时间（毫秒时间戳）
+    pub mtime: Option<i64>,
+    /// inode，仅Unix
+    pub inode: Option<u64>,
+    /// 权限模式，仅Unix
+    pub mode: Option<u32>,
 }
 
 impl FileTreeNode {
     /// 根据包ID获取文件树
     #[inline]
-    pub fn get_res(stmt: &mut Statement<'_>) -> anyhow::Result<Vec<Self>> {
+    pub fn get_res(stmt: &mut Statement<'_>, params: impl Params) -> Result<Vec<Self>> {
         use duckdb::types::Value;
 
-        let rows = stmt.query_map([], |row| {
+        let rows = stmt.query_map(params, |row| {
             let id: String = row.get(0)?;
             let name: String = row.get(1)?;
             let path_segments: Value = row.get(2)?;
@@ -125,6 +151,9 @@ impl FileTreeNode {
             let is_active: bool = row.get(6)?;
             let is_deployed: bool = row.get(7)?;
             let depth: i32 = row.get(8)?;
+            let mtime: Option<i64> = row.get(9)?;
+            let inode: Option<u64> = row.get(10)?;
+            let mode: Option<u32> = row.get(11)?;
 
             // 处理 path_segments 字段为 Vec<String>
             let path_segments = path_segments
@@ -146,6 +175,9 @@ impl FileTreeNode {
                 is_active,
                 is_deployed,
                 depth,
+                mtime,
+                inode,
+                mode,
             })
         })?;
 
@@ -175,10 +207,10 @@ pub struct FileOverTree {
 impl FileOverTree {
     /// 获取覆盖树文件列表
     #[inline]
-    pub fn get_res(stmt: &mut Statement<'_>) -> anyhow::Result<Vec<Self>> {
+    pub fn get_res(stmt: &mut Statement<'_>, params: impl Params) -> Result<Vec<Self>> {
         use duckdb::types::Value;
 
-        let rows = stmt.query_map([], |row| {
+        let rows = stmt.query_map(params, |row| {
             let file_id: String = row.get(0)?;
             let pack_id: String = row.get(1)?;
             let parent_id: Option<String> = row.get(2)?;
@@ -220,19 +252,21 @@ pub struct FileOverLinkList {
     pub path_relative: String,
     pub path_absolute: String,
     pub is_deployed: bool,
+    pub hash: Option<String>,
 }
 
 impl FileOverLinkList {
     /// 获取覆盖链接列表
     #[inline]
-    pub fn get_res(stmt: &mut Statement<'_>) -> anyhow::Result<Vec<Self>> {
-        let rows = stmt.query_map([], |row| {
+    pub fn get_res(stmt: &mut Statement<'_>, params: impl Params) -> Result<Vec<Self>> {
+        let rows = stmt.query_map(params, |row| {
             let file_id: String = row.get(0)?;
             let pack_id: String = row.get(1)?;
             let parent_id: Option<String> = row.get(2)?;
             let path_relative: String = row.get(3)?;
             let path_absolute: String = row.get(4)?;
             let is_deployed: bool = row.get(5)?;
+            let hash: Option<String> = row.get(6)?;
 
             Ok(FileOverLinkList {
                 file_id,
@@ -241,6 +275,7 @@ impl FileOverLinkList {
                 path_relative,
                 path_absolute,
                 is_deployed,
+                hash,
             })
         })?;
 
@@ -278,15 +313,14 @@ pub struct ConflictFileList {
 
 impl ConflictFileList {
     /// 从查询结果中获取冲突文件列表
-    pub fn get_res(stmt: &mut Statement<'_>) -> anyhow::Result<Vec<Self>> {
-        let rows = stmt.query_map([], |row| {
+    pub fn get_res(stmt: &mut Statement<'_>, params: impl Params) -> Result<Vec<Self>> {
+        let rows = stmt.query_map(params, |row| {
             let relative_path: String = row.get(0)?;
             let file_id: String = row.get(1)?;
             let pack_id: String = row.get(2)?;
 
             let files: Value = row.get(3)?;
-            let json_value = serde_json::to_value(&files).unwrap();
-            let files: Vec<ConflictFile> = serde_json::from_value(json_value).unwrap();
+            let files: Vec<ConflictFile> = from_value(3, &files)?;
 
             Ok(ConflictFileList {
                 relative_path,
@@ -310,8 +344,8 @@ pub struct HashEqualFiles {
 }
 
 impl HashEqualFiles {
-    pub fn get_res(stmt: &mut Statement<'_>) -> anyhow::Result<Vec<Self>> {
-        let rows = stmt.query_map([], |row| {
+    pub fn get_res(stmt: &mut Statement<'_>, params: impl Params) -> Result<Vec<Self>> {
+        let rows = stmt.query_map(params, |row| {
             let id: String = row.get(0)?;
             let pack_id: String = row.get(1)?;
             let pack_path: String = row.get(2)?;
@@ -329,14 +363,307 @@ impl HashEqualFiles {
         Ok(rows.filter_map(|r| r.ok()).collect())
     }
 
-    pub fn get_res_group(stmt: &mut Statement<'_>) -> anyhow::Result<Vec<Vec<HashEqualFiles>>> {
-        let rows = stmt.query_map([], |row| {
+    pub fn get_res_group(
+        stmt: &mut Statement<'_>,
+        params: impl Params,
+    ) -> Result<Vec<Vec<HashEqualFiles>>> {
+        let rows = stmt.query_map(params, |row| {
             let group: Value = row.get(0)?;
-            let binding = serde_json::to_value(&group).unwrap();
-            let v: Vec<HashEqualFiles> = serde_json::from_value(binding).unwrap();
-            Ok(v)
+            from_value(0, &group)
         })?;
 
         Ok(rows.filter_map(|r| r.ok()).collect::<Vec<_>>())
     }
 }
+
+/// 部署记录结构体
+#[derive(Debug, Serialize, Clone)]
+pub struct DeployedRecord {
+    /// 部署目标根目录
+    pub target_root: String,
+    /// 相对路径
+    pub path: String,
+    /// 来源文件id
+    pub file_id: String,
+    /// 来源包id
+    pub pack_id: String,
+    /// 来源文件绝对路径
+    pub source_path: String,
+    /// 部署方式
+    pub method: String,
+    /// 部署时的文件哈希
+    pub hash: Option<String>,
+    /// 部署时的文件大小，符号链接为空
+    pub size: Option<u64>,
+    /// 部署时的修改时间（毫秒时间戳），符号链接为空
+    pub mtime: Option<i64>,
+}
+
+impl DeployedRecord {
+    pub const SQL_GET_BY_TARGET: &'static str = r#"
+        SELECT target_root, path, file_id, pack_id, source_path, method, hash, size, mtime
+        FROM DeployedFile
+        WHERE target_root = ?
+        ORDER BY path
+        "#;
+
+    /// 获取部署记录列表
+    pub fn get_res(stmt: &mut Statement<'_>, target_root: &str) -> Result<Vec<Self>> {
+        let rows = stmt.query_map([target_root], |row| {
+            let target_root: String = row.get(0)?;
+            let path: String = row.get(1)?;
+            let file_id: String = row.get(2)?;
+            let pack_id: String = row.get(3)?;
+            let source_path: String = row.get(4)?;
+            let method: String = row.get(5)?;
+            let hash: Option<String> = row.get(6)?;
+            let size: Option<u64> = row.get(7)?;
+            let mtime: Option<i64> = row.get(8)?;
+
+            Ok(DeployedRecord {
+                target_root,
+                path,
+                file_id,
+                pack_id,
+                source_path,
+                method,
+                hash,
+                size,
+                mtime,
+            })
+        })?;
+
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+}
+
+/// 模式覆盖规则结构体
+#[derive(Debug, Serialize, Clone)]
+pub struct PatternRule {
+    /// 规则id
+    pub id: i64,
+    /// 规则类型 exact / prefix / glob
+    pub kind: String,
+    /// 路径、目录前缀或 glob
+    pub pattern: String,
+    /// 匹配的路径取自此包
+    pub pack_id: String,
+    /// 规则顺序，大的优先
+    pub rule_order: f64,
+    /// 是否启用
+    pub is_active: bool,
+}
+
+impl PatternRule {
+    pub const SQL_GET_ALL: &'static str = r#"
+        SELECT id, kind, pattern, pack_id, rule_order, is_active
+        FROM PriorityRule
+        WHERE profile_id = (SELECT id FROM Profile WHERE is_current)
+        ORDER BY rule_order DESC, id DESC
+        "#;
+
+    /// 获取当前方案的模式覆盖规则列表，按生效顺序排列
+    pub fn get_res(stmt: &mut Statement<'_>, params: impl Params) -> Result<Vec<Self>> {
+        let rows = stmt.query_map(params, |row| {
+            Ok(PatternRule {
+                id: row.get(0)?,
+                kind: row.get(1)?,
+                pattern: row.get(2)?,
+                pack_id: row.get(3)?,
+                rule_order: row.get(4)?,
+                is_active: row.get(5)?,
+            })
+        })?;
+
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+}
+
+/// 排除规则结构体
+#[derive(Debug, Serialize, Clone)]
+pub struct ExcludeRule {
+    /// 规则id
+    pub id: i64,
+    /// 规则类型 exact / prefix / glob
+    pub kind: String,
+    /// 路径、目录前缀或 glob
+    pub pattern: String,
+    /// 是否启用
+    pub is_active: bool,
+}
+
+impl ExcludeRule {
+    pub const SQL_GET_ALL: &'static str = r#"
+        SELECT id, kind, pattern, is_active
+        FROM ExcludeRule
+        WHERE profile_id = (SELECT id FROM Profile WHERE is_current)
+        ORDER BY id
+        "#;
+
+    /// 获取当前方案的排除规则列表
+    pub fn get_res(stmt: &mut Statement<'_>, params: impl Params) -> Result<Vec<Self>> {
+        let rows = stmt.query_map(params, |row| {
+            Ok(ExcludeRule {
+                id: row.get(0)?,
+                kind: row.get(1)?,
+                pattern: row.get(2)?,
+                is_active: row.get(3)?,
+            })
+        })?;
+
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+}
+
+/// 已入库的文件信息，重新扫描时用于比对
+#[derive(Debug, Clone)]
+pub struct KnownFile {
+    /// 文件id
+    pub id: Uuid,
+    /// 父节点id
+    pub parent_id: Option<Uuid>,
+    /// 相对路径切片
+    pub path_segments: Vec<String>,
+    /// 是否为目录
+    pub is_dir: bool,
+    /// 文件大小
+    pub size: Option<u64>,
+    /// 修改时间（毫秒时间戳）
+    pub mtime: Option<i64>,
+    /// 文件哈希值
+    pub hash: Option<String>,
+    /// 设备号
+    pub dev: Option<u64>,
+    /// inode
+    pub inode: Option<u64>,
+    /// 权限模式
+    pub mode: Option<u32>,
+}
+
+impl KnownFile {
+    pub const SQL_GET_BY_PACK: &'static str = r#"
+        SELECT id, parent_id, path_segments, is_dir, size, mtime, hash, dev, inode, mode
+        FROM FileNode
+        WHERE pack_id = ?
+        "#;
+
+    /// 获取包内已入库的文件
+    pub fn get_res(stmt: &mut Statement<'_>, pack_id: &str) -> Result<Vec<Self>> {
+        let parse_uuid = |i: usize, s: String| {
+            Uuid::parse_str(&s)
+                .map_err(|e| duckdb::Error::FromSqlConversionFailure(i, Type::Text, Box::new(e)))
+        };
+
+        let rows = stmt.query_map([pack_id], |row| {
+            let id: String = row.get(0)?;
+            let parent_id: Option<String> = row.get(1)?;
+            let path_segments: Value = row.get(2)?;
+            let is_dir: bool = row.get(3)?;
+            let size: Option<u64> = row.get(4)?;
+            let mtime: Option<i64> = row.get(5)?;
+            let hash: Option<String> = row.get(6)?;
+            let dev: Option<u64> = row.get(7)?;
+            let inode: Option<u64> = row.get(8)?;
+            let mode: Option<u32> = row.get(9)?;
+
+            // 处理 path_segments 字段为 Vec<String>，无法解析时返回错误，避免重新扫描时误判路径
+            let path_segments = path_segments
+                .into_inner_as::<Vec<Value>>()
+                .and_then(|segments| {
+                    segments
+                        .into_iter()
+                        .map(|v| v.into_inner_as::<String>())
+                        .collect::<Option<Vec<_>>>()
+                })
+                .ok_or(duckdb::Error::InvalidColumnType(
+                    2,
+                    "path_segments".to_string(),
+                    Type::List(Box::new(Type::Text)),
+                ))?;
+
+            Ok(KnownFile {
+                id: parse_uuid(0, id)?,
+                parent_id: parent_id.map(|s| parse_uuid(1, s)).transpose()?,
+                path_segments,
+                is_dir,
+                size,
+                mtime,
+                hash,
+                dev,
+                inode,
+                mode,
+            })
+        })?;
+
+        Ok(rows.collect::<duckdb::Result<Vec<_>>>()?)
+    }
+}
+
+/// 配置方案结构体
+#[derive(Debug, Serialize, Clone)]
+pub struct ProfileInfo {
+    /// 方案id
+    pub id: i64,
+    /// 方案名称
+    pub name: String,
+    /// 部署目标根目录
+    pub target_root: Option<String>,
+    /// 是否为当前方案
+    pub is_current: bool,
+}
+
+impl ProfileInfo {
+    pub const SQL_GET_ALL: &'static str = r#"
+        SELECT id, name, target_root, is_current
+        FROM Profile
+        ORDER BY id
+        "#;
+
+    /// 获取配置方案列表
+    pub fn get_res(stmt: &mut Statement<'_>, params: impl Params) -> Result<Vec<Self>> {
+        let rows = stmt.query_map(params, |row| {
+            Ok(ProfileInfo {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                target_root: row.get(2)?,
+                is_current: row.get(3)?,
+            })
+        })?;
+
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+}
+
+/// 包关系结构体
+#[derive(Debug, Serialize, Clone)]
+pub struct PackRelation {
+    /// 声明关系的包id
+    pub pack_id: String,
+    /// 关系类型 requires / incompatible / load_after / load_before
+    pub kind: String,
+    /// 目标包id
+    pub target_id: String,
+    /// 版本要求，仅 requires
+    pub version_req: Option<String>,
+}
+
+impl PackRelation {
+    pub const SQL_GET: &'static str = r#"
+        SELECT pack_id, kind, target_id, version_req
+        FROM PackRelation
+        "#;
+
+    /// 获取包关系列表
+    pub fn get_res(stmt: &mut Statement<'_>, params: impl Params) -> Result<Vec<Self>> {
+        let rows = stmt.query_map(params, |row| {
+            Ok(PackRelation {
+                pack_id: row.get(0)?,
+                kind: row.get(1)?,
+                target_id: row.get(2)?,
+                version_req: row.get(3)?,
+            })
+        })?;
+
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+}
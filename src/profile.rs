@@ -0,0 +1,159 @@
+use duckdb::{Connection, OptionalExt, params};
+
+use crate::{Error, ProfileInfo, Result, transaction};
+
+/// 当前方案id的子查询，供各处 SQL 拼接
+pub(crate) const SQL_CURRENT_PROFILE: &str = "(SELECT id FROM Profile WHERE is_current)";
+
+/// 配置方案的管理
+///
+/// 包顺序、包与文件的启用状态、独立覆盖规则、模式覆盖规则、排除规则与部署目标按方案记录，
+/// [`crate::ListPack`]、[`crate::ListTree`] 的修改与各视图的查询只作用于当前方案
+pub enum ListProfile<'a> {
+    /// 创建方案，所有包按添加顺序排列且不启用，参数为方案名称
+    Create(&'a str),
+    /// 复制方案的全部设置，新方案尚未部署，参数为源方案名称、新方案名称
+    CloneFrom(&'a str, &'a str),
+    /// 切换当前方案，参数为方案名称
+    Switch(&'a str),
+    /// 删除方案，不能删除当前方案，参数为方案名称
+    Remove(&'a str),
+    /// 设置方案的部署目标根目录，参数为方案名称、目录
+    SetTarget(&'a str, &'a str),
+}
+
+impl ListProfile<'_> {
+    /// 执行方案相关操作，涉及多条语句的操作在单个事务中执行
+    pub fn execute(&self, conn: &Connection) -> Result<()> {
+        use ListProfile::*;
+        match self {
+            Create(name) => transaction(conn, |conn| {
+                let id = Self::insert(conn, name, None)?;
+                conn.execute(
+                    "INSERT INTO PriorityPack (profile_id, pack_id, priority)
+                    SELECT ?::INTEGER, id, ROW_NUMBER() OVER (ORDER BY add_time, id) FROM FNPack;",
+                    [id],
+                )?;
+                Ok(())
+            }),
+            CloneFrom(from, name) => transaction(conn, |conn| {
+                let from = Self::id(conn, from)?;
+                let target_root: Option<String> = conn.query_row(
+                    "SELECT target_root FROM Profile WHERE id = ?;",
+                    [from],
+                    |row| row.get(0),
+                )?;
+                let id = Self::insert(conn, name, target_root.as_deref())?;
+                conn.execute(
+                    "INSERT INTO PriorityPack (profile_id, pack_id, priority, is_deployed, is_active)
+                    SELECT ?::INTEGER, pack_id, priority, FALSE, is_active
+                    FROM PriorityPack WHERE profile_id = ?;",
+                    [id, from],
+                )?;
+                conn.execute(
+                    "INSERT INTO PriorityFN (profile_id, path, main_id, pack_id, is_active)
+                    SELECT ?::INTEGER, path, main_id, pack_id, is_active
+                    FROM PriorityFN WHERE profile_id = ?;",
+                    [id, from],
+                )?;
+                conn.execute(
+                    "INSERT INTO ProfileFile (profile_id, file_id, is_active)
+                    SELECT ?::INTEGER, file_id, is_active
+                    FROM ProfileFile WHERE profile_id = ?;",
+                    [id, from],
+                )?;
+                conn.execute(
+                    "INSERT INTO PriorityRule (profile_id, kind, pattern, regex, pack_id, rule_order, is_active)
+                    SELECT ?::INTEGER, kind, pattern, regex, pack_id, rule_order, is_active
+                    FROM PriorityRule WHERE profile_id = ? ORDER BY id;",
+                    [id, from],
+                )?;
+                conn.execute(
+                    "INSERT INTO ExcludeRule (profile_id, kind, pattern, regex, is_active)
+                    SELECT ?::INTEGER, kind, pattern, regex, is_active
+                    FROM ExcludeRule WHERE profile_id = ? ORDER BY id;",
+                    [id, from],
+                )?;
+                Ok(())
+            }),
+            Switch(name) => transaction(conn, |conn| {
+                let id = Self::id(conn, name)?;
+                conn.execute("UPDATE Profile SET is_current = (id = ?);", [id])?;
+                Ok(())
+            }),
+            Remove(name) => transaction(conn, |conn| {
+                let id = Self::id(conn, name)?;
+                if Self::current(conn)?.id == id {
+                    return Err(Error::InvalidInput(format!("不能删除当前方案: {}", name)));
+                }
+                for table in [
+                    "PriorityPack",
+                    "PriorityFN",
+                    "ProfileFile",
+                    "PriorityRule",
+                    "ExcludeRule",
+                ] {
+                    conn.execute(
+                        &format!("DELETE FROM {} WHERE profile_id = ?;", table),
+                        [id],
+                    )?;
+                }
+                conn.execute("DELETE FROM Profile WHERE id = ?;", [id])?;
+                Ok(())
+            }),
+            SetTarget(name, target_root) => {
+                let id = Self::id(conn, name)?;
+                conn.execute(
+                    "UPDATE Profile SET target_root = ? WHERE id = ?;",
+                    params![target_root, id],
+                )?;
+                Ok(())
+            }
+        }
+    }
+
+    /// 获取所有方案
+    pub fn list(conn: &Connection) -> Result<Vec<ProfileInfo>> {
+        let mut stmt = conn.prepare(ProfileInfo::SQL_GET_ALL)?;
+        ProfileInfo::get_res(&mut stmt, [])
+    }
+
+    /// 获取当前方案
+    pub fn current(conn: &Connection) -> Result<ProfileInfo> {
+        Self::list(conn)?
+            .into_iter()
+            .find(|p| p.is_current)
+            .ok_or_else(|| Error::NotFound("当前方案".to_string()))
+    }
+
+    /// 按名称查找方案id
+    fn find(conn: &Connection, name: &str) -> Result<Option<i64>> {
+        let id = conn
+            .query_row("SELECT id FROM Profile WHERE name = ?;", [name], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        Ok(id)
+    }
+
+    /// 按名称查找方案id，不存在时返回 [`Error::NotFound`]
+    fn id(conn: &Connection, name: &str) -> Result<i64> {
+        Self::find(conn, name)?.ok_or_else(|| Error::NotFound(format!("方案: {}", name)))
+    }
+
+    /// 插入新方案并返回id，名称已存在时返回 [`Error::InvalidInput`]
+    fn insert(conn: &Connection, name: &str, target_root: Option<&str>) -> Result<i64> {
+        if name.trim().is_empty() {
+            return Err(Error::InvalidInput("方案名称不能为空".to_string()));
+        }
+        if Self::find(conn, name)?.is_some() {
+            return Err(Error::InvalidInput(format!("方案已存在: {}", name)));
+        }
+        let id = conn.query_row(
+            "INSERT INTO Profile (name, target_root) VALUES (?, ?) RETURNING id;",
+            params![name, target_root],
+            |row| row.get(0),
+        )?;
+        Ok(id)
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+};
+
+use serde::Serialize;
+
+use crate::{Error, IgnoreRules, Result};
+
+/// 取消令牌，克隆后共享同一状态
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// 创建未取消的令牌
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 请求取消，正在进行的扫描会尽快停止
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// 是否已请求取消
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// 已请求取消时返回 [`Error::Cancelled`]
+    pub fn check(&self) -> Result<()> {
+        match self.is_cancelled() {
+            true => Err(Error::Cancelled),
+            false => Ok(()),
+        }
+    }
+}
+
+/// 扫描进度
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanProgress {
+    /// 已发现的文件与目录数量
+    pub discovered: u64,
+    /// 已计算哈希的字节数
+    pub bytes_hashed: u64,
+    /// 当前处理的路径
+    pub path: String,
+}
+
+type ProgressFn = dyn Fn(&ScanProgress) + Send + Sync;
+
+/// 扫描选项，包括进度回调、取消令牌与忽略规则
+#[derive(Clone, Default)]
+pub struct ScanOptions {
+    progress: Option<Arc<ProgressFn>>,
+    cancel: CancelToken,
+    ignore: IgnoreRules,
+}
+
+impl ScanOptions {
+    /// 创建默认选项，不报告进度且不可取消
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置进度回调
+    ///
+    /// 回调在扫描线程中并发调用，需要在其他线程处理时可在回调中发送到通道
+    pub fn on_progress(mut self, f: impl Fn(&ScanProgress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(f));
+        self
+    }
+
+    /// 设置取消令牌
+    pub fn with_cancel(mut self, token: CancelToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// 追加忽略规则，与包根目录的 .vfsignore 一起生效，.vfsignore 优先
+    pub fn with_ignore(mut self, rules: IgnoreRules) -> Self {
+        self.ignore.extend(rules);
+        self
+    }
+
+    /// 在已有规则之前加入规则，已有规则优先
+    pub(crate) fn with_base_ignore(mut self, mut rules: IgnoreRules) -> Self {
+        rules.extend(std::mem::take(&mut self.ignore));
+        self.ignore = rules;
+        self
+    }
+
+    /// 取消令牌
+    pub fn cancel_token(&self) -> &CancelToken {
+        &self.cancel
+    }
+
+    /// 忽略规则
+    pub fn ignore(&self) -> &IgnoreRules {
+        &self.ignore
+    }
+}
+
+/// 单次扫描的进度计数，由 [`crate::PackSource`] 的实现报告发现的条目与哈希进度
+pub struct ScanState<'a> {
+    options: &'a ScanOptions,
+    discovered: AtomicU64,
+    bytes_hashed: AtomicU64,
+}
+
+impl<'a> ScanState<'a> {
+    /// 按扫描选项创建，计数从 0 开始
+    pub fn new(options: &'a ScanOptions) -> Self {
+        Self {
+            options,
+            discovered: AtomicU64::new(0),
+            bytes_hashed: AtomicU64::new(0),
+        }
+    }
+
+    /// 已请求取消时返回 [`Error::Cancelled`]
+    pub fn check(&self) -> Result<()> {
+        self.options.cancel.check()
+    }
+
+    /// 记录发现的路径
+    pub fn discovered(&self, path: &Path) {
+        let discovered = self.discovered.fetch_add(1, Ordering::Relaxed) + 1;
+        self.report(path, discovered, self.bytes_hashed.load(Ordering::Relaxed));
+    }
+
+    /// 记录已计算哈希的字节数
+    pub fn hashed(&self, path: &Path, bytes: u64) {
+        let bytes_hashed = self.bytes_hashed.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.report(path, self.discovered.load(Ordering::Relaxed), bytes_hashed);
+    }
+
+    fn report(&self, path: &Path, discovered: u64, bytes_hashed: u64) {
+        if let Some(f) = &self.options.progress {
+            f(&ScanProgress {
+                discovered,
+                bytes_hashed,
+                path: path.to_string_lossy().to_string(),
+            });
+        }
+    }
+}
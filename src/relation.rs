@@ -0,0 +1,475 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+};
+
+use duckdb::{Connection, params};
+use hashbrown::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{Error, ListPack, PackRelation, Result, profile::SQL_CURRENT_PROFILE, transaction};
+
+/// 包之间的关系类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelationKind {
+    /// 依赖目标包，可附带版本要求，目标包应排在其前
+    Requires,
+    /// 不能与目标包同时启用
+    Incompatible,
+    /// 目标包启用时应排在其前，目标包不存在时忽略
+    LoadAfter,
+    /// 目标包启用时应排在其后，目标包不存在时忽略
+    LoadBefore,
+}
+
+impl RelationKind {
+    /// 数据库中保存的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Requires => "requires",
+            Self::Incompatible => "incompatible",
+            Self::LoadAfter => "load_after",
+            Self::LoadBefore => "load_before",
+        }
+    }
+
+    /// 从数据库中保存的名称解析
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "requires" => Some(Self::Requires),
+            "incompatible" => Some(Self::Incompatible),
+            "load_after" => Some(Self::LoadAfter),
+            "load_before" => Some(Self::LoadBefore),
+            _ => None,
+        }
+    }
+}
+
+/// 版本比较运算符
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VersionOp {
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// 版本要求，如 `>=1.2`、`<2`、`=1.0.3`，没有运算符时按 `=` 处理
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionReq {
+    op: VersionOp,
+    version: String,
+}
+
+impl VersionReq {
+    /// 解析版本要求，格式不正确时返回 [`Error::InvalidInput`]
+    pub fn parse(req: &str) -> Result<Self> {
+        let req = req.trim();
+        let (op, version) = [
+            (">=", VersionOp::Ge),
+            ("<=", VersionOp::Le),
+            ("==", VersionOp::Eq),
+            (">", VersionOp::Gt),
+            ("<", VersionOp::Lt),
+            ("=", VersionOp::Eq),
+        ]
+        .into_iter()
+        .find_map(|(prefix, op)| req.strip_prefix(prefix).map(|rest| (op, rest.trim())))
+        .unwrap_or((VersionOp::Eq, req));
+
+        let valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+' | '_');
+        if version.is_empty() || !version.chars().all(valid) {
+            return Err(Error::InvalidInput(format!("无效的版本要求: {}", req)));
+        }
+        Ok(Self {
+            op,
+            version: version.to_string(),
+        })
+    }
+
+    /// 版本是否满足要求
+    pub fn matches(&self, version: &str) -> bool {
+        let ord = compare_versions(version, &self.version);
+        match self.op {
+            VersionOp::Eq => ord == Ordering::Equal,
+            VersionOp::Gt => ord == Ordering::Greater,
+            VersionOp::Ge => ord != Ordering::Less,
+            VersionOp::Lt => ord == Ordering::Less,
+            VersionOp::Le => ord != Ordering::Greater,
+        }
+    }
+}
+
+/// 逐段比较版本号，数字段按数值比较，其余按字符串比较，缺少的段视为 0
+///
+/// 忽略开头的 `v`，`1.2` 与 `1.2.0` 相等
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let split = |v: &str| -> Vec<String> {
+        v.trim()
+            .trim_start_matches(['v', 'V'])
+            .split(['.', '-', '+', '_'])
+            .map(|s| s.to_string())
+            .collect()
+    };
+    let (a, b) = (split(a), split(b));
+    for i in 0..a.len().max(b.len()) {
+        let x = a.get(i).map_or("0", |s| s.as_str());
+        let y = b.get(i).map_or("0", |s| s.as_str());
+        let ord = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            _ => x.cmp(y),
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    Ordering::Equal
+}
+
+/// 包关系校验发现的问题
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RelationIssue {
+    /// 依赖的包未入库或在当前方案中未启用
+    MissingDependency {
+        pack_id: String,
+        target_id: String,
+        /// 依赖的包是否已入库
+        installed: bool,
+    },
+    /// 依赖的包版本不满足要求
+    VersionMismatch {
+        pack_id: String,
+        target_id: String,
+        version_req: String,
+        /// 依赖的包的版本，未记录时为空
+        found: Option<String>,
+    },
+    /// 与启用的包不兼容
+    Incompatible { pack_id: String, target_id: String },
+    /// 当前顺序违反 pack_id 声明的依赖或加载顺序
+    OrderViolation { pack_id: String, target_id: String },
+}
+
+/// 自动排序的结果
+#[derive(Debug, Default, Serialize, Clone)]
+pub struct SortReport {
+    /// 排序后的包id，按优先级从低到高，存在循环时为空
+    pub order: Vec<String>,
+    /// 顺序是否有变化并已写回
+    pub changed: bool,
+    /// 构成循环的包id，每组为一个循环
+    pub cycles: Vec<Vec<String>>,
+}
+
+/// 当前方案中包的状态
+struct PackState {
+    priority: f64,
+    is_active: bool,
+    version: Option<String>,
+}
+
+/// 包之间的依赖、不兼容与加载顺序声明
+///
+/// 关系由包id标识，目标包可以尚未入库
+pub enum ListRelation<'a> {
+    /// 添加或替换关系，参数为包id、关系类型、目标包id、版本要求
+    ///
+    /// 版本要求只能用于 [`RelationKind::Requires`]
+    Add(&'a str, RelationKind, &'a str, Option<&'a str>),
+    /// 移除关系，参数为包id、关系类型、目标包id
+    Remove(&'a str, RelationKind, &'a str),
+    /// 移除包声明的所有关系
+    RemoveByPackId(&'a str),
+}
+
+impl ListRelation<'_> {
+    const SQL_GET_PACKS: &'static str = r#"
+        SELECT pp.pack_id, pp.priority, pp.is_active, i.version
+        FROM PriorityPack pp
+        LEFT JOIN InfoBase i ON pp.pack_id = i.id
+        WHERE pp.profile_id = "#;
+
+    /// 执行关系相关操作
+    pub fn execute(&self, conn: &Connection) -> Result<()> {
+        use ListRelation::*;
+        match self {
+            Add(pack_id, kind, target_id, version_req) => {
+                Uuid::parse_str(pack_id)?;
+                Uuid::parse_str(target_id)?;
+                if pack_id == target_id {
+                    return Err(Error::InvalidInput(format!(
+                        "包不能与自身建立关系: {}",
+                        pack_id
+                    )));
+                }
+                if let Some(req) = version_req {
+                    if *kind != RelationKind::Requires {
+                        return Err(Error::InvalidInput(format!(
+                            "{} 关系不能指定版本要求",
+                            kind.as_str()
+                        )));
+                    }
+                    VersionReq::parse(req)?;
+                }
+                conn.execute(
+                    "INSERT OR REPLACE INTO PackRelation (pack_id, kind, target_id, version_req)
+                    VALUES (?, ?, ?, ?);",
+                    params![pack_id, kind.as_str(), target_id, version_req],
+                )?;
+            }
+            Remove(pack_id, kind, target_id) => {
+                conn.execute(
+                    "DELETE FROM PackRelation WHERE pack_id = ? AND kind = ? AND target_id = ?;",
+                    params![pack_id, kind.as_str(), target_id],
+                )?;
+            }
+            RemoveByPackId(pack_id) => {
+                conn.execute("DELETE FROM PackRelation WHERE pack_id = ?;", [*pack_id])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 获取包声明的所有关系
+    pub fn list(conn: &Connection, pack_id: &str) -> Result<Vec<PackRelation>> {
+        let sql = format!(
+            "{} WHERE pack_id = ? ORDER BY kind, target_id;",
+            PackRelation::SQL_GET
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        PackRelation::get_res(&mut stmt, [pack_id])
+    }
+
+    /// 按当前方案的启用状态与顺序校验所有启用的包声明的关系
+    pub fn validate(conn: &Connection) -> Result<Vec<RelationIssue>> {
+        let packs = Self::packs(conn)?;
+        let relations = Self::relations(conn)?;
+
+        let mut issues = vec![];
+        for r in relations {
+            let Some(pack) = packs.get(&r.pack_id).filter(|p| p.is_active) else {
+                continue;
+            };
+            let Some(kind) = RelationKind::parse(&r.kind) else {
+                log::warn!("未知的包关系: {}", r.kind);
+                continue;
+            };
+            let target = packs.get(&r.target_id);
+            let active_target = target.filter(|t| t.is_active);
+            let (pack_id, target_id) = (r.pack_id.clone(), r.target_id.clone());
+            match kind {
+                RelationKind::Requires => {
+                    let Some(t) = active_target else {
+                        issues.push(RelationIssue::MissingDependency {
+                            pack_id,
+                            target_id,
+                            installed: target.is_some(),
+                        });
+                        continue;
+                    };
+                    if let Some(req) = &r.version_req {
+                        let satisfied = match &t.version {
+                            Some(version) => VersionReq::parse(req)?.matches(version),
+                            None => false,
+                        };
+                        if !satisfied {
+                            issues.push(RelationIssue::VersionMismatch {
+                                pack_id: pack_id.clone(),
+                                target_id: target_id.clone(),
+                                version_req: req.clone(),
+                                found: t.version.clone(),
+                            });
+                        }
+                    }
+                    if t.priority > pack.priority {
+                        issues.push(RelationIssue::OrderViolation { pack_id, target_id });
+                    }
+                }
+                RelationKind::Incompatible => {
+                    if active_target.is_some() {
+                        issues.push(RelationIssue::Incompatible { pack_id, target_id });
+                    }
+                }
+                RelationKind::LoadAfter => {
+                    if active_target.is_some_and(|t| t.priority > pack.priority) {
+                        issues.push(RelationIssue::OrderViolation { pack_id, target_id });
+                    }
+                }
+                RelationKind::LoadBefore => {
+                    if active_target.is_some_and(|t| t.priority < pack.priority) {
+                        issues.push(RelationIssue::OrderViolation { pack_id, target_id });
+                    }
+                }
+            }
+        }
+        Ok(issues)
+    }
+
+    /// 按关系声明重新排列当前方案中的包顺序
+    ///
+    /// requires 与 load_after 的目标包排在前，load_before 的目标包排在后，其余保持原有的相对顺序。
+    /// 新的优先级从优先级序列中依次取得，大于现有最大值，可再用 [`ListPack::ResetPriority`] 整理；
+    /// 存在循环时不修改顺序，只在结果中报告
+    pub fn auto_sort(conn: &Connection) -> Result<SortReport> {
+        transaction(conn, |conn| {
+            let packs = Self::packs(conn)?;
+            let mut current = packs.iter().collect::<Vec<_>>();
+            current.sort_by(|a, b| a.1.priority.total_cmp(&b.1.priority).then(a.0.cmp(b.0)));
+            let ids = current.into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+            let index = ids
+                .iter()
+                .enumerate()
+                .map(|(i, id)| (id.as_str(), i))
+                .collect::<HashMap<_, _>>();
+
+            // 先加载的包 -> 后加载的包
+            let mut edges = HashSet::new();
+            for r in Self::relations(conn)? {
+                let (Some(&pack), Some(&target)) = (
+                    index.get(r.pack_id.as_str()),
+                    index.get(r.target_id.as_str()),
+                ) else {
+                    continue;
+                };
+                match RelationKind::parse(&r.kind) {
+                    Some(RelationKind::Requires | RelationKind::LoadAfter) => {
+                        edges.insert((target, pack));
+                    }
+                    Some(RelationKind::LoadBefore) => {
+                        edges.insert((pack, target));
+                    }
+                    _ => {}
+                }
+            }
+
+            let order = match topo_sort(ids.len(), &edges) {
+                Ok(order) => order,
+                Err(cycles) => {
+                    return Ok(SortReport {
+                        cycles: cycles
+                            .into_iter()
+                            .map(|c| c.into_iter().map(|i| ids[i].to_string()).collect())
+                            .collect(),
+                        ..Default::default()
+                    });
+                }
+            };
+            let changed = order.iter().enumerate().any(|(i, &v)| i != v);
+            if changed {
+                // 从优先级序列取值，之后添加的包不会与重排后的优先级冲突
+                for &v in &order {
+                    ListPack::SetPriority(ids[v], ListPack::next_priority(conn)?).execute(conn)?;
+                }
+            }
+            Ok(SortReport {
+                order: order.into_iter().map(|i| ids[i].to_string()).collect(),
+                changed,
+                cycles: vec![],
+            })
+        })
+    }
+
+    /// 当前方案中所有包的状态
+    fn packs(conn: &Connection) -> Result<HashMap<String, PackState>> {
+        let sql = format!("{}{};", Self::SQL_GET_PACKS, SQL_CURRENT_PROFILE);
+        let mut stmt = conn.prepare(&sql)?;
+        let packs = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    PackState {
+                        priority: row.get(1)?,
+                        is_active: row.get(2)?,
+                        version: row.get(3)?,
+                    },
+                ))
+            })?
+            .collect::<duckdb::Result<HashMap<_, _>>>()?;
+        Ok(packs)
+    }
+
+    /// 所有包声明的关系
+    fn relations(conn: &Connection) -> Result<Vec<PackRelation>> {
+        let sql = format!(
+            "{} ORDER BY pack_id, kind, target_id;",
+            PackRelation::SQL_GET
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        PackRelation::get_res(&mut stmt, [])
+    }
+}
+
+/// 稳定的拓扑排序，可选的节点中总是先取下标最小的
+///
+/// 存在循环时返回所有包含多个节点的强连通分量
+fn topo_sort(n: usize, edges: &HashSet<(usize, usize)>) -> Result<Vec<usize>, Vec<Vec<usize>>> {
+    let mut adj = vec![vec![]; n];
+    let mut indegree = vec![0; n];
+    for &(from, to) in edges {
+        adj[from].push(to);
+        indegree[to] += 1;
+    }
+
+    let mut ready = (0..n)
+        .filter(|&v| indegree[v] == 0)
+        .map(Reverse)
+        .collect::<BinaryHeap<_>>();
+    let mut order = Vec::with_capacity(n);
+    while let Some(Reverse(v)) = ready.pop() {
+        order.push(v);
+        for &next in &adj[v] {
+            indegree[next] -= 1;
+            if indegree[next] == 0 {
+                ready.push(Reverse(next));
+            }
+        }
+    }
+    if order.len() == n {
+        return Ok(order);
+    }
+
+    // 未能排序的节点位于循环中或排在循环之后，用 Kosaraju 算法找出其中的循环
+    let blocked = indegree.iter().map(|&d| d > 0).collect::<Vec<_>>();
+    let mut radj = vec![vec![]; n];
+    for &(from, to) in edges {
+        if blocked[from] && blocked[to] {
+            radj[to].push(from);
+        }
+    }
+    let mut seen = vec![false; n];
+    let mut finished = vec![];
+    for v in (0..n).filter(|&v| blocked[v]) {
+        if !seen[v] {
+            dfs(v, &adj, &blocked, &mut seen, &mut finished);
+        }
+    }
+    let mut seen = vec![false; n];
+    let mut cycles = vec![];
+    for &v in finished.iter().rev() {
+        if !seen[v] {
+            let mut component = vec![];
+            dfs(v, &radj, &blocked, &mut seen, &mut component);
+            if component.len() > 1 {
+                component.sort();
+                cycles.push(component);
+            }
+        }
+    }
+    cycles.sort();
+    Err(cycles)
+}
+
+/// 在 nodes 标记的子图中深度优先遍历，节点按完成顺序追加到 out
+fn dfs(v: usize, adj: &[Vec<usize>], nodes: &[bool], seen: &mut [bool], out: &mut Vec<usize>) {
+    seen[v] = true;
+    for &next in &adj[v] {
+        if nodes[next] && !seen[next] {
+            dfs(next, adj, nodes, seen, out);
+        }
+    }
+    out.push(v);
+}
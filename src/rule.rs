@@ -0,0 +1,64 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result, ignore::glob_regex};
+
+/// 覆盖规则与排除规则的匹配类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PatternKind {
+    /// 完整路径，只匹配该路径本身
+    Exact,
+    /// 目录前缀，匹配目录本身及其中的所有内容
+    Prefix,
+    /// 相对包根目录的 glob，支持 `*`、`?`、`[...]` 与 `**`
+    Glob,
+}
+
+impl PatternKind {
+    /// 数据库中保存的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Exact => "exact",
+            Self::Prefix => "prefix",
+            Self::Glob => "glob",
+        }
+    }
+
+    /// 从数据库中保存的名称解析
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "exact" => Some(Self::Exact),
+            "prefix" => Some(Self::Prefix),
+            "glob" => Some(Self::Glob),
+            _ => None,
+        }
+    }
+
+    /// 生成完整匹配相对路径（以 '/' 分隔）的正则，由覆盖树视图使用
+    pub fn to_regex(&self, pattern: &str) -> Result<String> {
+        let segments = pattern
+            .split(|c| c == '/' || c == '\\')
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>();
+        if segments.is_empty() {
+            return Err(Error::InvalidInput(format!("空的覆盖规则: {}", pattern)));
+        }
+
+        let escaped = || {
+            segments
+                .iter()
+                .map(|s| regex::escape(s))
+                .collect::<Vec<_>>()
+                .join("/")
+        };
+        let re = match self {
+            Self::Exact => format!("^{}$", escaped()),
+            Self::Prefix => format!("^{}(?:/.*)?$", escaped()),
+            Self::Glob => glob_regex(&segments.join("/"), true),
+        };
+        Regex::new(&re)
+            .map_err(|e| Error::InvalidInput(format!("无效的覆盖规则 {}: {}", pattern, e)))?;
+        Ok(re)
+    }
+}
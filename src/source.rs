@@ -0,0 +1,330 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use duckdb::{Connection, OptionalExt};
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::{
+    Archive, ArchiveKind, Error, IGNORE_FILE, IgnoreRules, Result, ScanState,
+    model_insert::{UnixMeta, hash_file_with, hash_reader, millis},
+};
+
+/// 包来源中的条目
+#[derive(Debug, Clone)]
+pub struct SourceEntry {
+    /// 相对路径切片
+    pub path_segments: Vec<String>,
+    /// 是否是文件夹
+    pub is_dir: bool,
+    /// 大小，文件夹为空
+    pub size: Option<u64>,
+    /// 修改时间（毫秒时间戳）
+    pub mtime: Option<i64>,
+    /// Unix 文件元数据
+    pub unix: Option<UnixMeta>,
+    /// 哈希，来源在枚举时顺带计算时给出，否则由 [`PackSource::hash`] 计算
+    pub hash: Option<String>,
+}
+
+/// 包来源的类型，决定入库后能否从数据库中恢复来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceKind {
+    /// 本地目录
+    Dir,
+    /// 压缩包
+    Archive,
+    /// 内存等自定义来源，不能从数据库中恢复
+    Custom,
+}
+
+impl SourceKind {
+    /// 数据库中保存的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Dir => "dir",
+            Self::Archive => "archive",
+            Self::Custom => "custom",
+        }
+    }
+
+    /// 从数据库中保存的名称解析
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "dir" => Some(Self::Dir),
+            "archive" => Some(Self::Archive),
+            "custom" => Some(Self::Custom),
+            _ => None,
+        }
+    }
+}
+
+/// 包的来源，提供条目枚举与文件读取
+///
+/// 本地目录、压缩包与内存中的文件树都通过此接口入库、部署与校验
+pub trait PackSource: fmt::Debug + Send + Sync {
+    /// 保存到数据库中的来源路径
+    fn base_path(&self) -> &str;
+
+    /// 来源类型，本库以外实现的来源为 [`SourceKind::Custom`]
+    fn source_kind(&self) -> SourceKind {
+        SourceKind::Custom
+    }
+
+    /// 压缩包来源的格式，其他来源为空
+    fn archive_kind(&self) -> Option<ArchiveKind> {
+        None
+    }
+
+    /// 来源根目录下的 [`IGNORE_FILE`]，不存在时返回空规则
+    fn ignore_rules(&self) -> Result<IgnoreRules>;
+
+    /// 枚举所有未被忽略的条目，每个条目调用 [`ScanState::discovered`]，并响应取消
+    fn entries(&self, ignore: &IgnoreRules, state: &ScanState) -> Result<Vec<SourceEntry>>;
+
+    /// 打开文件读取内容
+    fn open(&self, segments: &[String]) -> Result<Box<dyn Read + Send + '_>>;
+
+    /// 计算文件的哈希并报告进度
+    fn hash(&self, entry: &SourceEntry, state: &ScanState) -> Result<String> {
+        let path = Path::new(self.base_path()).join(entry.path_segments.join("/"));
+        hash_reader(self.open(&entry.path_segments)?, &path, state)
+    }
+
+    /// 文件在本地磁盘上的路径，只有存在时才能以链接方式部署
+    fn local_path(&self, _segments: &[String]) -> Option<PathBuf> {
+        None
+    }
+
+    /// 将多个文件写入各自的目标位置，目标不能已存在
+    ///
+    /// 默认逐个打开文件复制，只能顺序读取的来源应在一次读取中写出全部文件
+    fn extract(&self, files: &[(Vec<String>, PathBuf)]) -> Result<()> {
+        for (segments, dst) in files {
+            let mut reader = self.open(segments)?;
+            std::io::copy(&mut reader, &mut std::fs::File::create_new(dst)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// 读取已入库包的来源，包不存在时返回 [`Error::NotFound`]
+///
+/// 内存等自定义来源不能从数据库中恢复，返回 [`Error::InvalidInput`]
+pub fn pack_source(conn: &Connection, pack_id: &str) -> Result<Arc<dyn PackSource>> {
+    let (base_path, source_kind, archive_kind) = conn
+        .query_row(
+            "SELECT base_path, source_kind, archive_kind FROM FNPack WHERE id = ?;",
+            [pack_id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            },
+        )
+        .optional()?
+        .ok_or_else(|| Error::NotFound(format!("包: {}", pack_id)))?;
+    match SourceKind::parse(&source_kind) {
+        Some(SourceKind::Dir) => Ok(Arc::new(DirSource::new(&base_path))),
+        Some(SourceKind::Archive) => match archive_kind.as_deref().and_then(ArchiveKind::parse) {
+            Some(kind) => Ok(Arc::new(Archive::new(&base_path, kind))),
+            None => Err(Error::InvalidInput(format!(
+                "未知的压缩包格式: {}",
+                archive_kind.unwrap_or_default()
+            ))),
+        },
+        Some(SourceKind::Custom) => Err(Error::InvalidInput(format!(
+            "包 {} 的来源 {} 不能从数据库中恢复，需要重新提供来源",
+            pack_id, base_path
+        ))),
+        None => Err(Error::InvalidInput(format!(
+            "未知的包来源类型: {}",
+            source_kind
+        ))),
+    }
+}
+
+/// 本地目录来源
+#[derive(Debug, Clone)]
+pub struct DirSource {
+    root: String,
+}
+
+impl DirSource {
+    /// 创建以 root 为根目录的来源
+    pub fn new(root: &str) -> Self {
+        Self {
+            root: root.to_string(),
+        }
+    }
+
+    /// 文件的本地路径，与覆盖树视图中的绝对路径拼接方式一致
+    fn file_path(&self, segments: &[String]) -> PathBuf {
+        PathBuf::from(format!("{}/{}", self.root, segments.join("/")))
+    }
+}
+
+impl PackSource for DirSource {
+    fn base_path(&self) -> &str {
+        &self.root
+    }
+
+    fn source_kind(&self) -> SourceKind {
+        SourceKind::Dir
+    }
+
+    fn ignore_rules(&self) -> Result<IgnoreRules> {
+        IgnoreRules::from_pack_root(Path::new(&self.root))
+    }
+
+    fn entries(&self, ignore: &IgnoreRules, state: &ScanState) -> Result<Vec<SourceEntry>> {
+        let root = &self.root;
+        WalkDir::new(root)
+            .follow_links(false)
+            .min_depth(1)
+            .into_iter()
+            // 被忽略的目录不再深入
+            .filter_entry(|e| {
+                !relative_segments(root, e.path())
+                    .is_ok_and(|s| ignore.skips(&s, e.file_type().is_dir()))
+            })
+            .map(|entry| -> Result<SourceEntry> {
+                state.check()?;
+                let entry = entry?;
+                state.discovered(entry.path());
+                let is_dir = entry.file_type().is_dir();
+                let metadata = entry.metadata()?;
+                Ok(SourceEntry {
+                    path_segments: relative_segments(root, entry.path())?,
+                    is_dir,
+                    size: (!is_dir).then(|| metadata.len()),
+                    mtime: metadata.modified().ok().map(millis),
+                    unix: UnixMeta::from_metadata(&metadata),
+                    hash: None,
+                })
+            })
+            .collect()
+    }
+
+    fn open(&self, segments: &[String]) -> Result<Box<dyn Read + Send + '_>> {
+        Ok(Box::new(std::fs::File::open(self.file_path(segments))?))
+    }
+
+    fn hash(&self, entry: &SourceEntry, state: &ScanState) -> Result<String> {
+        let path = self.file_path(&entry.path_segments);
+        hash_file_with(&path, entry.size.unwrap_or(0), state)
+    }
+
+    fn local_path(&self, segments: &[String]) -> Option<PathBuf> {
+        Some(self.file_path(segments))
+    }
+}
+
+/// 内存中的文件树来源，用于在不访问磁盘的情况下构造包
+#[derive(Debug, Clone, Default)]
+pub struct MemorySource {
+    name: String,
+    /// 路径切片 -> 文件内容，目录为空
+    entries: BTreeMap<Vec<String>, Option<Vec<u8>>>,
+}
+
+impl MemorySource {
+    /// 创建空的文件树，name 作为来源路径保存到数据库
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// 添加文件，路径以 '/' 分隔，缺失的上级目录自动补全
+    pub fn with_file(mut self, path: &str, data: impl Into<Vec<u8>>) -> Self {
+        let segments = split_path(path);
+        self.add_dirs(&segments[..segments.len().saturating_sub(1)]);
+        self.entries.insert(segments, Some(data.into()));
+        self
+    }
+
+    /// 添加目录，路径以 '/' 分隔，缺失的上级目录自动补全
+    pub fn with_dir(mut self, path: &str) -> Self {
+        self.add_dirs(&split_path(path));
+        self
+    }
+
+    fn add_dirs(&mut self, segments: &[String]) {
+        for depth in 1..=segments.len() {
+            self.entries
+                .entry(segments[..depth].to_vec())
+                .or_insert(None);
+        }
+    }
+}
+
+impl PackSource for MemorySource {
+    fn base_path(&self) -> &str {
+        &self.name
+    }
+
+    fn ignore_rules(&self) -> Result<IgnoreRules> {
+        match self.entries.get([IGNORE_FILE.to_string()].as_slice()) {
+            Some(Some(data)) => IgnoreRules::parse(&String::from_utf8_lossy(data)),
+            _ => Ok(IgnoreRules::new()),
+        }
+    }
+
+    fn entries(&self, ignore: &IgnoreRules, state: &ScanState) -> Result<Vec<SourceEntry>> {
+        let mut entries = vec![];
+        for (segments, data) in &self.entries {
+            state.check()?;
+            if ignore.skips(segments, data.is_none()) {
+                continue;
+            }
+            state.discovered(&Path::new(&self.name).join(segments.join("/")));
+            entries.push(SourceEntry {
+                path_segments: segments.clone(),
+                is_dir: data.is_none(),
+                size: data.as_ref().map(|d| d.len() as u64),
+                mtime: None,
+                unix: None,
+                hash: None,
+            });
+        }
+        Ok(entries)
+    }
+
+    fn open(&self, segments: &[String]) -> Result<Box<dyn Read + Send + '_>> {
+        match self.entries.get(segments) {
+            Some(Some(data)) => Ok(Box::new(data.as_slice())),
+            _ => Err(Error::NotFound(format!(
+                "{} 中的文件: {}",
+                self.name,
+                segments.join("/")
+            ))),
+        }
+    }
+}
+
+/// 以 '/' 或 '\' 分隔的路径切片，空段被跳过
+fn split_path(path: &str) -> Vec<String> {
+    path.split(|c| c == '/' || c == '\\')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// 包内路径相对包根目录的路径切片
+fn relative_segments(root: &str, path: &Path) -> Result<Vec<String>> {
+    let curr_path = path.to_string_lossy();
+    let relative_path = curr_path
+        .strip_prefix(root)
+        .ok_or_else(|| Error::InvalidInput(format!("路径不在包目录内: {}", curr_path)))?;
+    Ok(split_path(relative_path))
+}
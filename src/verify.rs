@@ -0,0 +1,203 @@
+use std::sync::Arc;
+
+use duckdb::Connection;
+use hashbrown::HashMap;
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+use serde::Serialize;
+
+use crate::{
+    Error, IgnoreRules, KnownFile, PackSource, Result, ScanOptions, ScanState, pack_source,
+};
+
+/// 内容与记录不符的文件
+#[derive(Debug, Serialize, Clone)]
+pub struct ModifiedFile {
+    /// 文件id
+    pub file_id: String,
+    /// 相对路径
+    pub path: String,
+    /// 记录的大小
+    pub expected_size: Option<u64>,
+    /// 实际大小
+    pub actual_size: u64,
+    /// 记录的哈希
+    pub expected_hash: Option<String>,
+    /// 实际哈希，读取失败时为空
+    pub actual_hash: Option<String>,
+}
+
+/// 包完整性校验报告
+#[derive(Debug, Default, Serialize, Clone)]
+pub struct VerifyReport {
+    /// 包id
+    pub pack_id: String,
+    /// 包路径
+    pub base_path: String,
+    /// 有记录但来源中缺失的文件（相对路径）
+    pub missing: Vec<String>,
+    /// 大小或哈希与记录不符的文件
+    pub modified: Vec<ModifiedFile>,
+    /// 来源中存在但没有记录的文件（相对路径）
+    pub extra: Vec<String>,
+    /// 来源无法读取时的错误，此时不比对文件
+    pub error: Option<String>,
+}
+
+impl VerifyReport {
+    /// 包是否与记录完全一致
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+            && self.missing.is_empty()
+            && self.modified.is_empty()
+            && self.extra.is_empty()
+    }
+}
+
+/// 包完整性校验，重新计算包内所有文件的哈希并与记录比对
+///
+/// 来源无法从数据库中恢复的包（如自定义来源）在报告中记录错误，不影响其余包的校验
+pub enum VerifyPack<'a> {
+    /// 校验指定id的包
+    ById(&'a str),
+    /// 使用指定的来源校验指定id的包，用于自定义来源的包
+    ByIdWithSource(&'a str, Arc<dyn PackSource>),
+    /// 校验所有包
+    All,
+}
+
+impl VerifyPack<'_> {
+    const SQL_GET_PACKS: &'static str = "SELECT id, base_path FROM FNPack ORDER BY add_time";
+
+    const SQL_GET_PACK: &'static str = "SELECT id, base_path FROM FNPack WHERE id = ?";
+
+    /// 执行校验，返回每个包的校验报告
+    pub fn execute(&self, conn: &Connection) -> Result<Vec<VerifyReport>> {
+        let packs = match self {
+            Self::ById(id) | Self::ByIdWithSource(id, _) => {
+                let mut stmt = conn.prepare(Self::SQL_GET_PACK)?;
+                let packs = stmt
+                    .query_map([*id], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<duckdb::Result<Vec<(String, String)>>>()?;
+                if packs.is_empty() {
+                    return Err(Error::NotFound(format!("包: {}", id)));
+                }
+                packs
+            }
+            Self::All => {
+                let mut stmt = conn.prepare(Self::SQL_GET_PACKS)?;
+                stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<duckdb::Result<Vec<(String, String)>>>()?
+            }
+        };
+
+        let global = IgnoreRules::global(conn)?;
+        let mut reports = vec![];
+        for (pack_id, base_path) in packs {
+            let mut stmt = conn.prepare(KnownFile::SQL_GET_BY_PACK)?;
+            let known = KnownFile::get_res(&mut stmt, &pack_id)?;
+            let source = match self {
+                Self::ByIdWithSource(_, source) => source.clone(),
+                _ => match pack_source(conn, &pack_id) {
+                    Ok(source) => source,
+                    Err(Error::InvalidInput(e)) => {
+                        log::warn!("无法校验包 {}: {}", base_path, e);
+                        reports.push(VerifyReport {
+                            pack_id,
+                            base_path,
+                            error: Some(e),
+                            ..Default::default()
+                        });
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                },
+            };
+            reports.push(Self::verify(
+                pack_id,
+                base_path,
+                source.as_ref(),
+                &known,
+                &global,
+            )?);
+        }
+        Ok(reports)
+    }
+
+    /// 校验单个包，来源无法读取时在报告中记录错误
+    fn verify(
+        pack_id: String,
+        base_path: String,
+        source: &dyn PackSource,
+        known: &[KnownFile],
+        global: &IgnoreRules,
+    ) -> Result<VerifyReport> {
+        let options = ScanOptions::default();
+        let state = ScanState::new(&options);
+        // 已记录的文件不受忽略规则影响，忽略规则只用于判断多余文件
+        let mut entries = match source.entries(&IgnoreRules::new(), &state) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::warn!("读取包来源失败 {}: {}", base_path, e);
+                return Ok(VerifyReport {
+                    pack_id,
+                    base_path,
+                    error: Some(e.to_string()),
+                    ..Default::default()
+                });
+            }
+        };
+        let mut ignore = global.clone();
+        ignore.extend(source.ignore_rules()?);
+
+        let recorded = known
+            .iter()
+            .map(|k| (k.path_segments.clone(), k))
+            .collect::<HashMap<_, _>>();
+        // 只为有记录的文件计算哈希，读取失败时哈希为空
+        entries
+            .par_iter_mut()
+            .filter(|e| !e.is_dir && e.hash.is_none() && recorded.contains_key(&e.path_segments))
+            .for_each(|e| e.hash = source.hash(e, &state).ok());
+
+        let entries = entries
+            .into_iter()
+            .filter(|e| !e.is_dir)
+            .map(|e| (e.path_segments.clone(), e))
+            .collect::<HashMap<_, _>>();
+
+        let mut report = VerifyReport {
+            pack_id,
+            base_path,
+            ..Default::default()
+        };
+        for k in known.iter().filter(|k| !k.is_dir) {
+            let relative = k.path_segments.join("/");
+            let Some(entry) = entries.get(&k.path_segments) else {
+                report.missing.push(relative);
+                continue;
+            };
+            if entry.size != k.size || entry.hash.is_none() || entry.hash != k.hash {
+                report.modified.push(ModifiedFile {
+                    file_id: k.id.to_string(),
+                    path: relative,
+                    expected_size: k.size,
+                    actual_size: entry.size.unwrap_or(0),
+                    expected_hash: k.hash.clone(),
+                    actual_hash: entry.hash.clone(),
+                });
+            }
+        }
+
+        // 没有记录的文件，被忽略的文件不算作多余文件
+        report.extra = entries
+            .keys()
+            .filter(|s| !recorded.contains_key(*s) && !ignore.skips(s, false))
+            .map(|s| s.join("/"))
+            .collect();
+
+        report.missing.sort();
+        report.modified.sort_by(|a, b| a.path.cmp(&b.path));
+        report.extra.sort();
+        Ok(report)
+    }
+}
@@ -0,0 +1,11 @@
+-- 未记录版本的旧数据库表结构（引入版本记录之前），仅供升级测试使用，不要修改
+-- 基础信息
+CREATE TABLE IF NOT EXISTS InfoBase (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL,                    
+    tags TEXT[],                               
+    description TEXT,                       
+    author TEXT,                            
+    version TEXT,                           
+    update_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+);
@@ -1,10 +1,18 @@
 #[cfg(test)]
 mod test {
 
-    use duckdb::arrow::{array::RecordBatch, util::pretty::print_batches};
+    use duckdb::{
+        arrow::{array::RecordBatch, util::pretty::print_batches},
+        params_from_iter,
+    };
+    use std::time::Instant;
     use uuid::Uuid;
 
-    use vfslink_base::{FNDB, ListPack, ListTree, ViewOverTree, ViewPack};
+    use vfslink_base::{
+        Batch, CancelToken, DeployAction, DeployMethod, Deployer, Error, FNDB, FNPack, FileNode,
+        IGNORE_FILE, IgnoreRules, ListIgnore, ListPack, ListTree, MemorySource, ScanOptions,
+        ScanProgress, VerifyPack, ViewOverTree, ViewPack,
+    };
 
     const PATH: &str = "example1.db";
 
@@ -12,9 +20,312 @@ mod test {
     #[test]
     fn test_init() {
         let mut fndb = FNDB::new(PATH);
-        fndb.connect_rw();
-        fndb.init();
-        fndb.init_view();
+        fndb.connect_rw().unwrap();
+        fndb.init().unwrap();
+        fndb.init_view().unwrap();
+    }
+
+    // 表结构迁移
+    #[test]
+    fn test_migration() {
+        use vfslink_base::migration::{SCHEMA_VERSION, migrate, schema_version};
+
+        // 新数据库连接时迁移到最新版本
+        let mut fndb = FNDB::new(":memory:");
+        fndb.connect_rw().unwrap();
+        let conn = fndb.try_conn().unwrap();
+        assert_eq!(schema_version(conn).unwrap(), SCHEMA_VERSION);
+        assert_eq!(migrate(conn).unwrap(), SCHEMA_VERSION);
+
+        // 拒绝更新版本的数据库
+        conn.execute(
+            "INSERT INTO SchemaVersion (version) VALUES (?);",
+            [SCHEMA_VERSION + 1],
+        )
+        .unwrap();
+        assert!(matches!(
+            migrate(conn),
+            Err(Error::SchemaTooNew { found, .. }) if found == SCHEMA_VERSION + 1
+        ));
+
+        // 旧版本创建、没有版本记录的数据库补齐新增的列
+        let conn = duckdb::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE FileNode (
+                id UUID PRIMARY KEY,
+                pack_id UUID NOT NULL,
+                parent_id UUID,
+                name VARCHAR NOT NULL,
+                path_segments VARCHAR[] NOT NULL,
+                is_dir bool NOT NULL,
+                is_active BOOLEAN DEFAULT TRUE NOT NULL,
+                is_deployed BOOLEAN DEFAULT FALSE NOT NULL,
+                size uint64,
+                hash VARCHAR,
+                full_path VARCHAR GENERATED ALWAYS AS (array_to_string(path_segments, '/')) VIRTUAL,
+                depth INT GENERATED ALWAYS AS (array_length(path_segments)) VIRTUAL,
+            );",
+        )
+        .unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 0);
+        migrate(&conn).unwrap();
+        conn.execute_batch("SELECT mtime, dev, inode, mode FROM FileNode;")
+            .unwrap();
+    }
+
+    // 旧版本的数据库升级到最新版本，原有数据保留
+    //
+    // 表结构取自 tests/fixtures 中保存的旧版本建表语句
+    #[test]
+    fn test_migration_upgrade() {
+        use std::sync::Arc;
+        use vfslink_base::migration::{SCHEMA_VERSION, schema_version};
+        use vfslink_base::model_insert::InfoBase;
+
+        // 引入版本记录之前的表结构
+        const V0: &[&str] = &[
+            include_str!("fixtures/v0/core.sql"),
+            include_str!("fixtures/v0/info.sql"),
+        ];
+        // 版本 1 的表结构
+        const V1: &[&str] = &[
+            include_str!("fixtures/v1/core.sql"),
+            include_str!("fixtures/v0/info.sql"),
+            include_str!("fixtures/v1/deploy.sql"),
+        ];
+        const PACK: &str = "00000000-0000-0000-0000-000000000001";
+        const DIR: &str = "00000000-0000-0000-0000-000000000011";
+        const FILE_A: &str = "00000000-0000-0000-0000-000000000012";
+        const FILE_B: &str = "00000000-0000-0000-0000-000000000013";
+
+        for (version, schema) in [(0, V0), (1, V1)] {
+            let path = std::env::temp_dir().join(format!("vfslink_upgrade_v{}.db", version));
+            let _ = std::fs::remove_file(&path);
+            {
+                let conn = duckdb::Connection::open(&path).unwrap();
+                for sql in schema {
+                    conn.execute_batch(sql).unwrap();
+                }
+                if version == 1 {
+                    conn.execute_batch(
+                        "CREATE TABLE SchemaVersion (
+                            version INTEGER PRIMARY KEY,
+                            applied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                        );
+                        INSERT INTO SchemaVersion (version) VALUES (1);",
+                    )
+                    .unwrap();
+                }
+                conn.execute_batch(&format!(
+                    "INSERT INTO FNPack (id, base_path) VALUES ('{PACK}', '/packs/a');
+                    INSERT INTO InfoBase (id, name) VALUES ('{PACK}', 'a');
+                    INSERT INTO FileNode (id, pack_id, parent_id, name, path_segments, is_dir, is_active, size, hash)
+                    VALUES
+                        ('{DIR}', '{PACK}', NULL, 'dir', ['dir'], TRUE, TRUE, NULL, NULL),
+                        ('{FILE_A}', '{PACK}', '{DIR}', 'a.txt', ['dir', 'a.txt'], FALSE, TRUE, 1, 'h1'),
+                        ('{FILE_B}', '{PACK}', '{DIR}', 'b.txt', ['dir', 'b.txt'], FALSE, FALSE, 2, 'h2');
+                    INSERT INTO PriorityPack (pack_id, is_active) VALUES ('{PACK}', TRUE);
+                    INSERT INTO PriorityFN (path, main_id, pack_id) VALUES ('dir/a.txt', '{FILE_A}', '{PACK}');"
+                ))
+                .unwrap();
+                if version == 1 {
+                    conn.execute_batch(&format!(
+                        "INSERT INTO DeployedFile (target_root, path, file_id, pack_id, source_path, method, hash)
+                        VALUES ('/target', 'dir/a.txt', '{FILE_A}', '{PACK}', '/packs/a/dir/a.txt', 'copy', 'h1');
+                        INSERT INTO DeployedDir (target_root, path) VALUES ('/target', 'dir');
+                        INSERT INTO DeployBackup (target_root, path, backup_path, hash)
+                        VALUES ('/target', 'dir/a.txt', '/backup/dir/a.txt', 'h0');
+                        INSERT INTO DeployTarget (target_root, session_start) VALUES ('/target', 1);"
+                    ))
+                    .unwrap();
+                }
+                assert_eq!(schema_version(&conn).unwrap(), version);
+            }
+
+            // 读写连接时迁移到最新版本
+            let mut fndb = FNDB::new(path.to_str().unwrap());
+            fndb.connect_rw().unwrap();
+            fndb.init_view().unwrap();
+            let conn = fndb.try_conn().unwrap();
+            assert_eq!(schema_version(conn).unwrap(), SCHEMA_VERSION);
+
+            // 新增的列为空，生成列仍可读取
+            let (full_path, depth, mtime, dev, inode, mode): (
+                String,
+                i32,
+                Option<i64>,
+                Option<u64>,
+                Option<u64>,
+                Option<u32>,
+            ) = conn
+                .query_row(
+                    "SELECT full_path, depth, mtime, dev, inode, mode FROM FileNode WHERE id = ?;",
+                    [FILE_A],
+                    |row| {
+                        Ok((
+                            row.get(0)?,
+                            row.get(1)?,
+                            row.get(2)?,
+                            row.get(3)?,
+                            row.get(4)?,
+                            row.get(5)?,
+                        ))
+                    },
+                )
+                .unwrap();
+            assert_eq!((full_path.as_str(), depth), ("dir/a.txt", 2));
+            assert_eq!((mtime, dev, inode, mode), (None, None, None, None));
+            let (source_kind, archive_kind): (String, Option<String>) = conn
+                .query_row("SELECT source_kind, archive_kind FROM FNPack;", [], |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })
+                .unwrap();
+            assert_eq!((source_kind.as_str(), archive_kind), ("dir", None));
+            let staging: i64 = conn
+                .query_row(
+                    "SELECT count(*) FROM duckdb_tables() WHERE table_name = 'FileNodeStaging';",
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(staging, 0);
+
+            // 原有的包顺序、独立覆盖规则与文件启用状态归入默认方案
+            let (profile_id, name): (i32, String) = conn
+                .query_row(
+                    "SELECT id, name FROM Profile WHERE is_current;",
+                    [],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .unwrap();
+            assert_eq!(name, "default");
+            let (pack_profile, priority, is_active): (i32, f64, bool) = conn
+                .query_row(
+                    "SELECT profile_id, priority, is_active FROM PriorityPack;",
+                    [],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .unwrap();
+            assert_eq!((pack_profile, priority, is_active), (profile_id, 1.0, true));
+            let (fn_profile, path_fn): (i32, String) = conn
+                .query_row("SELECT profile_id, path FROM PriorityFN;", [], |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })
+                .unwrap();
+            assert_eq!((fn_profile, path_fn.as_str()), (profile_id, "dir/a.txt"));
+            let inactive: Vec<(i32, String, bool)> = conn
+                .prepare("SELECT profile_id, file_id::VARCHAR, is_active FROM ProfileFile;")
+                .unwrap()
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                .unwrap()
+                .collect::<duckdb::Result<_>>()
+                .unwrap();
+            assert_eq!(inactive, vec![(profile_id, FILE_B.to_string(), false)]);
+
+            // 覆盖树视图可以读取迁移后的数据
+            let tree = ViewOverTree::GetAllPaths
+                .execute(conn)
+                .unwrap()
+                .as_file_over_link_list();
+            let a = tree
+                .iter()
+                .find(|r| r.path_relative == "dir/a.txt")
+                .unwrap();
+            assert_eq!(a.file_id, FILE_A);
+
+            // 版本 1 的部署记录保留，新增的列为空
+            if version == 1 {
+                let (size, mtime): (Option<u64>, Option<i64>) = conn
+                    .query_row("SELECT size, mtime FROM DeployedFile;", [], |row| {
+                        Ok((row.get(0)?, row.get(1)?))
+                    })
+                    .unwrap();
+                assert_eq!((size, mtime), (None, None));
+                for table in [
+                    "DeployedFile",
+                    "DeployedDir",
+                    "DeployBackup",
+                    "DeployTarget",
+                ] {
+                    let count: i64 = conn
+                        .query_row(&format!("SELECT count(*) FROM {};", table), [], |row| {
+                            row.get(0)
+                        })
+                        .unwrap();
+                    assert_eq!(count, 1, "{}", table);
+                }
+            }
+
+            // 迁移后新加入的包排在原有的包之后
+            let info = InfoBase {
+                id: Uuid::new_v4(),
+                name: "new".to_string(),
+                tag: vec![],
+                description: None,
+                author: None,
+                version: None,
+            };
+            let id = info.id.to_string();
+            ListPack::InsertSource(
+                Arc::new(MemorySource::new("memory://new").with_file("dir/a.txt", "new")),
+                info,
+            )
+            .execute(conn)
+            .unwrap();
+            let priority: f64 = conn
+                .query_row(
+                    "SELECT priority FROM PriorityPack WHERE pack_id = ?;",
+                    [&id],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(priority, 2.0);
+
+            fndb.disconnect_w().unwrap();
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+
+    // 只读连接与跨线程共享
+    #[test]
+    fn test_connection_modes() {
+        let path = std::env::temp_dir().join("vfslink_ro.db");
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let mut fndb = FNDB::new(path);
+        fndb.connect_rw().unwrap();
+        fndb.init_view().unwrap();
+        fndb.disconnect_w().unwrap();
+
+        // 只读连接可以查询但不能写入
+        fndb.connect_ro().unwrap();
+        assert!(fndb.is_read_only());
+        ViewPack::GetPackInfo
+            .execute(fndb.try_conn().unwrap())
+            .unwrap();
+        let res = ListPack::ResetPriority.execute(fndb.try_conn().unwrap());
+        assert!(res.is_err());
+        fndb.disconnect_w().unwrap();
+
+        // 工作线程使用克隆连接写入，主线程同时查询
+        fndb.connect_rw().unwrap();
+        let handle = fndb.handle().unwrap();
+        let worker = {
+            let handle = handle.clone();
+            std::thread::spawn(move || {
+                let conn = handle.connect().unwrap();
+                for _ in 0..10 {
+                    ListPack::ResetPriority.execute(&conn).unwrap();
+                }
+            })
+        };
+        for _ in 0..10 {
+            handle
+                .with_conn(|conn| ViewPack::GetPackInfo.execute(conn))
+                .unwrap();
+        }
+        worker.join().unwrap();
     }
 
     mod table {
@@ -25,9 +336,10 @@ mod test {
         #[test]
         fn test_show_pack_list() {
             let mut fndb = FNDB::new(PATH);
-            fndb.connect_rw();
+            fndb.connect_rw().unwrap();
             let mut stmt = fndb
-                .get_conn()
+                .try_conn()
+                .unwrap()
                 .prepare(&ViewPack::GetPackInfo.to_sql())
                 .unwrap();
 
@@ -36,7 +348,7 @@ mod test {
 
             // 获取值测试
             let res = ViewPack::GetPackInfo
-                .execute(fndb.get_conn())
+                .execute(fndb.try_conn().unwrap())
                 .unwrap()
                 .as_pack_info();
             println!("{:#?}", res);
@@ -46,7 +358,7 @@ mod test {
         #[test]
         fn test_insert_pack() {
             let mut fndb = FNDB::new(PATH);
-            fndb.connect_rw();
+            fndb.connect_rw().unwrap();
 
             // 创建并保存第一个包
             let info = InfoBase {
@@ -57,7 +369,9 @@ mod test {
                 author: Some("作者1".to_string()),
                 version: Some("1.0.0".to_string()),
             };
-            ListPack::Insert(r"D:\CloudMusic\1", info).execute(fndb.get_conn());
+            ListPack::Insert(r"D:\CloudMusic\1", info)
+                .execute(fndb.try_conn().unwrap())
+                .unwrap();
 
             // 创建并保存第二个包
             let info = InfoBase {
@@ -68,7 +382,9 @@ mod test {
                 author: Some("作者2".to_string()),
                 version: Some("1.0.0".to_string()),
             };
-            ListPack::Insert(r"D:\CloudMusic\2", info).execute(fndb.get_conn());
+            ListPack::Insert(r"D:\CloudMusic\2", info)
+                .execute(fndb.try_conn().unwrap())
+                .unwrap();
 
             // 创建并保存第三个包
             let info = InfoBase {
@@ -79,7 +395,9 @@ mod test {
                 author: Some("作者3".to_string()),
                 version: Some("1.0.0".to_string()),
             };
-            ListPack::Insert(r"D:\CloudMusic\3", info).execute(fndb.get_conn());
+            ListPack::Insert(r"D:\CloudMusic\3", info)
+                .execute(fndb.try_conn().unwrap())
+                .unwrap();
 
             // 创建并保存第四个包
             let info = InfoBase {
@@ -90,11 +408,14 @@ mod test {
                 author: Some("作者4".to_string()),
                 version: Some("1.0.0".to_string()),
             };
-            ListPack::Insert(r"D:\CloudMusic\4", info).execute(fndb.get_conn());
+            ListPack::Insert(r"D:\CloudMusic\4", info)
+                .execute(fndb.try_conn().unwrap())
+                .unwrap();
 
             // 查询
             let mut stmt = fndb
-                .get_conn()
+                .try_conn()
+                .unwrap()
                 .prepare(&ViewPack::GetPackInfo.to_sql())
                 .unwrap();
             let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
@@ -105,11 +426,12 @@ mod test {
         #[test]
         fn test_delete_pack() {
             let mut fndb = FNDB::new(PATH);
-            fndb.connect_rw();
+            fndb.connect_rw().unwrap();
 
             // 查询
             let mut stmt = fndb
-                .get_conn()
+                .try_conn()
+                .unwrap()
                 .prepare(&ViewPack::GetPackInfo.to_sql())
                 .unwrap();
             let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
@@ -132,13 +454,14 @@ mod test {
                 .collect::<Vec<_>>();
             let id = id[0].clone();
 
-            let conn = fndb.get_conn();
+            let conn = fndb.try_conn().unwrap();
             let sql = ListPack::RemoveById(&id);
-            sql.execute(conn);
+            sql.execute(conn).unwrap();
 
             // 查询
             let mut stmt = fndb
-                .get_conn()
+                .try_conn()
+                .unwrap()
                 .prepare(&ViewPack::GetPackInfo.to_sql())
                 .unwrap();
             let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
@@ -149,11 +472,12 @@ mod test {
         #[test]
         fn test_update_pack_status() {
             let mut fndb = FNDB::new(PATH);
-            fndb.connect_rw();
+            fndb.connect_rw().unwrap();
 
             // 查询包列表
             let mut stmt = fndb
-                .get_conn()
+                .try_conn()
+                .unwrap()
                 .prepare(&ViewPack::GetPackInfo.to_sql())
                 .unwrap();
             let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
@@ -168,12 +492,15 @@ mod test {
 
             for id in ids {
                 let id = id.unwrap();
-                ListPack::SetActive(&id, true).execute(fndb.get_conn());
+                ListPack::SetActive(&id, true)
+                    .execute(fndb.try_conn().unwrap())
+                    .unwrap();
             }
 
             // 查询包列表
             let mut stmt = fndb
-                .get_conn()
+                .try_conn()
+                .unwrap()
                 .prepare(&ViewPack::GetPackInfo.to_sql())
                 .unwrap();
             let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
@@ -184,11 +511,12 @@ mod test {
         #[test]
         fn test_update_pack_priority() {
             let mut fndb = FNDB::new(PATH);
-            fndb.connect_rw();
+            fndb.connect_rw().unwrap();
 
             // 查询包列表
             let mut stmt = fndb
-                .get_conn()
+                .try_conn()
+                .unwrap()
                 .prepare(&ViewPack::GetPackInfo.to_sql())
                 .unwrap();
             let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
@@ -206,11 +534,14 @@ mod test {
                 .unwrap();
 
             // 更新包优先级
-            ListPack::SetPriority(&id, 10.1).execute(fndb.get_conn());
+            ListPack::SetPriority(&id, 10.1)
+                .execute(fndb.try_conn().unwrap())
+                .unwrap();
 
             // 查询包列表
             let mut stmt = fndb
-                .get_conn()
+                .try_conn()
+                .unwrap()
                 .prepare(&ViewPack::GetPackInfo.to_sql())
                 .unwrap();
             let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
@@ -221,10 +552,11 @@ mod test {
         #[test]
         fn test_update_pack_info() {
             let mut fndb = FNDB::new(PATH);
-            fndb.connect_rw();
+            fndb.connect_rw().unwrap();
             // 查询包列表
             let mut stmt = fndb
-                .get_conn()
+                .try_conn()
+                .unwrap()
                 .prepare(&ViewPack::GetPackInfo.to_sql())
                 .unwrap();
             let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
@@ -250,10 +582,13 @@ mod test {
                 version: Some("1.0.0".to_string()),
             };
 
-            ListPack::SetInfo(&id, info).execute(fndb.get_conn());
+            ListPack::SetInfo(&id, info)
+                .execute(fndb.try_conn().unwrap())
+                .unwrap();
             // 查询包列表
             let mut stmt = fndb
-                .get_conn()
+                .try_conn()
+                .unwrap()
                 .prepare(&ViewPack::GetPackInfo.to_sql())
                 .unwrap();
             let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
@@ -264,11 +599,12 @@ mod test {
         #[test]
         fn test_get_file_tree() {
             let mut fndb = FNDB::new(PATH);
-            fndb.connect_rw();
+            fndb.connect_rw().unwrap();
 
             // 查询包列表
             let mut stmt = fndb
-                .get_conn()
+                .try_conn()
+                .unwrap()
                 .prepare(&ViewPack::GetPackInfo.to_sql())
                 .unwrap();
             let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
@@ -287,15 +623,19 @@ mod test {
 
             // 查询文件树
             let mut stmt = fndb
-                .get_conn()
+                .try_conn()
+                .unwrap()
                 .prepare(&ViewPack::GetFileById(&id).to_sql())
                 .unwrap();
-            let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
+            let rbs: Vec<RecordBatch> = stmt
+                .query_arrow(params_from_iter(ViewPack::GetFileById(&id).params()))
+                .unwrap()
+                .collect();
             print_batches(&rbs).unwrap();
 
             // 获取详细文件信息
             let rows = ViewPack::GetFileById(&id)
-                .execute(fndb.get_conn())
+                .execute(fndb.try_conn().unwrap())
                 .unwrap()
                 .as_file_tree_node();
             println!("{:#?}", rows);
@@ -305,11 +645,12 @@ mod test {
         #[test]
         fn test_update_file_status() {
             let mut fndb = FNDB::new(PATH);
-            fndb.connect_rw();
+            fndb.connect_rw().unwrap();
 
             // 查询包列表
             let mut stmt = fndb
-                .get_conn()
+                .try_conn()
+                .unwrap()
                 .prepare(&ViewPack::GetPackInfo.to_sql())
                 .unwrap();
             let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
@@ -328,10 +669,14 @@ mod test {
 
             // 查询文件树
             let mut stmt = fndb
-                .get_conn()
+                .try_conn()
+                .unwrap()
                 .prepare(&ViewPack::GetFileById(&id).to_sql())
                 .unwrap();
-            let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
+            let rbs: Vec<RecordBatch> = stmt
+                .query_arrow(params_from_iter(ViewPack::GetFileById(&id).params()))
+                .unwrap()
+                .collect();
             print_batches(&rbs).unwrap();
 
             // 获取第一条记录的ID
@@ -346,36 +691,633 @@ mod test {
                 .unwrap();
 
             // 更新文件状态
-            ListTree::SetActive(&id, false).execute(fndb.get_conn());
+            ListTree::SetActive(&id, false)
+                .execute(fndb.try_conn().unwrap())
+                .unwrap();
 
             // 查询文件树
             let mut stmt = fndb
-                .get_conn()
+                .try_conn()
+                .unwrap()
                 .prepare(&ViewPack::GetFileById(&id).to_sql())
                 .unwrap();
-            let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
+            let rbs: Vec<RecordBatch> = stmt
+                .query_arrow(params_from_iter(ViewPack::GetFileById(&id).params()))
+                .unwrap()
+                .collect();
             print_batches(&rbs).unwrap();
         }
 
+        // 重新扫描包
+        #[test]
+        fn test_rescan_pack() {
+            let mut fndb = FNDB::new(PATH);
+            fndb.connect_rw().unwrap();
+
+            let id = ViewPack::GetPackInfo
+                .execute(fndb.try_conn().unwrap())
+                .unwrap()
+                .as_pack_info()[0]
+                .id
+                .clone();
+            let before = ViewPack::GetFileById(&id)
+                .execute(fndb.try_conn().unwrap())
+                .unwrap()
+                .as_file_tree_node();
+
+            ListPack::Rescan(&id)
+                .execute(fndb.try_conn().unwrap())
+                .unwrap();
+
+            // 目录未变化时文件id与启用状态保持不变
+            let after = ViewPack::GetFileById(&id)
+                .execute(fndb.try_conn().unwrap())
+                .unwrap()
+                .as_file_tree_node();
+            assert_eq!(before.len(), after.len());
+            for (a, b) in before.iter().zip(after.iter()) {
+                assert_eq!(a.id, b.id);
+                assert_eq!(a.is_active, b.is_active);
+            }
+        }
+
+        // 校验包的完整性
+        #[test]
+        fn test_verify_pack() {
+            let mut fndb = FNDB::new(PATH);
+            fndb.connect_rw().unwrap();
+
+            let reports = VerifyPack::All.execute(fndb.try_conn().unwrap()).unwrap();
+            println!("{:#?}", reports);
+
+            let id = &reports[0].pack_id;
+            let report = VerifyPack::ById(id)
+                .execute(fndb.try_conn().unwrap())
+                .unwrap();
+            assert_eq!(report.len(), 1);
+            assert_eq!(report[0].is_ok(), reports[0].is_ok());
+        }
+
+        // Appender 批量写入与逐行写入的耗时对比
+        #[test]
+        fn test_bulk_insert_bench() {
+            let dir = std::env::temp_dir().join("vfslink_bulk_bench");
+            let _ = std::fs::remove_dir_all(&dir);
+            for i in 0..20 {
+                let sub = dir.join(format!("dir{}", i));
+                std::fs::create_dir_all(&sub).unwrap();
+                for j in 0..100 {
+                    std::fs::write(sub.join(format!("file{}.txt", j)), format!("{}-{}", i, j))
+                        .unwrap();
+                }
+            }
+            let mut pack = FNPack::new(dir.to_str().unwrap(), Uuid::new_v4());
+            pack.walk_dir().unwrap();
+            let nodes = pack.children();
+            assert_eq!(nodes.len(), 20 * 101);
+
+            let open = || {
+                let mut fndb = FNDB::new(":memory:");
+                fndb.connect_rw().unwrap();
+                fndb.init().unwrap();
+                fndb
+            };
+            let paths = |fndb: &FNDB| {
+                let mut stmt = fndb
+                    .try_conn()
+                    .unwrap()
+                    .prepare("SELECT full_path FROM FileNode ORDER BY full_path")
+                    .unwrap();
+                stmt.query_map([], |row| row.get::<_, String>(0))
+                    .unwrap()
+                    .map(|r| r.unwrap())
+                    .collect::<Vec<_>>()
+            };
+
+            // 逐行写入
+            let per_row = open();
+            let start = Instant::now();
+            for node in nodes {
+                per_row
+                    .try_conn()
+                    .unwrap()
+                    .execute(&node.to_sql(), params_from_iter(node.params()))
+                    .unwrap();
+            }
+            let per_row_time = start.elapsed();
+
+            // Appender 批量写入
+            let bulk = open();
+            let start = Instant::now();
+            let count = FileNode::append_all(bulk.try_conn().unwrap(), nodes).unwrap();
+            let bulk_time = start.elapsed();
+
+            println!("逐行写入: {:?}，批量写入: {:?}", per_row_time, bulk_time);
+            assert_eq!(count, nodes.len());
+            assert_eq!(paths(&per_row), paths(&bulk));
+
+            // 中转表不保留在数据库中，重复写入失败后不留下残余数据
+            let conn = bulk.try_conn().unwrap();
+            assert!(FileNode::append_all(conn, nodes).is_err());
+            assert_eq!(paths(&bulk).len(), nodes.len());
+            let staging: i64 = conn
+                .query_row(
+                    "SELECT count(*) FROM duckdb_tables() WHERE table_name = 'FileNodeStaging';",
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(staging, 0);
+        }
+
+        // 扫描进度与取消
+        #[test]
+        fn test_scan_progress_cancel() {
+            let dir = std::env::temp_dir().join("vfslink_scan_progress");
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(dir.join("sub")).unwrap();
+            std::fs::write(dir.join("a.txt"), "a").unwrap();
+            std::fs::write(dir.join("sub").join("b.bin"), vec![7u8; 3 * 1024 * 1024]).unwrap();
+
+            // 进度回调通过通道发送到当前线程
+            let (tx, rx) = std::sync::mpsc::channel();
+            let options = ScanOptions::new().on_progress(move |p: &ScanProgress| {
+                tx.send(p.clone()).unwrap();
+            });
+            let mut pack = FNPack::new(dir.to_str().unwrap(), Uuid::new_v4());
+            pack.walk_dir_with(&options).unwrap();
+            drop(options);
+            let last = rx
+                .iter()
+                .max_by_key(|p| (p.discovered, p.bytes_hashed))
+                .unwrap();
+            assert_eq!(last.discovered, 3);
+            assert_eq!(last.bytes_hashed, 1 + 3 * 1024 * 1024);
+
+            // 已取消时不写入数据库
+            let mut fndb = FNDB::new(":memory:");
+            fndb.connect_rw().unwrap();
+            fndb.init_view().unwrap();
+            let token = CancelToken::new();
+            token.cancel();
+            let info = InfoBase {
+                id: Uuid::new_v4(),
+                name: "取消的包".to_string(),
+                tag: vec![],
+                description: None,
+                author: None,
+                version: None,
+            };
+            let res = ListPack::Insert(dir.to_str().unwrap(), info).execute_with(
+                fndb.try_conn().unwrap(),
+                &ScanOptions::new().with_cancel(token),
+            );
+            assert!(matches!(res, Err(Error::Cancelled)));
+            let packs = ViewPack::GetPackInfo
+                .execute(fndb.try_conn().unwrap())
+                .unwrap()
+                .as_pack_info();
+            assert!(packs.is_empty());
+        }
+
+        // 忽略规则
+        #[test]
+        fn test_ignore_rules() {
+            let seg = |p: &str| p.split('/').map(|s| s.to_string()).collect::<Vec<_>>();
+            let rules =
+                IgnoreRules::parse("# 注释\n*.tmp\n!keep.tmp\n/build/\ndocs/**/*.md\nThumbs.db\n")
+                    .unwrap();
+            assert!(rules.is_ignored(&seg("a/b.tmp"), false));
+            assert!(!rules.is_ignored(&seg("a/keep.tmp"), false));
+            assert!(rules.is_ignored(&seg("build/x.dll"), false));
+            assert!(!rules.is_ignored(&seg("a/build"), true));
+            assert!(!rules.is_ignored(&seg("build"), false));
+            assert!(rules.is_ignored(&seg("docs/readme.md"), false));
+            assert!(rules.is_ignored(&seg("docs/a/b/readme.md"), false));
+            assert!(rules.is_ignored(&seg("x/Thumbs.db"), false));
+            assert!(!rules.is_ignored(&seg("x/thumbs.db"), false));
+
+            let dir = std::env::temp_dir().join("vfslink_ignore");
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(dir.join(".git")).unwrap();
+            std::fs::create_dir_all(dir.join("data")).unwrap();
+            std::fs::write(dir.join(".git").join("HEAD"), "ref").unwrap();
+            std::fs::write(dir.join("data").join("a.esp"), "a").unwrap();
+            std::fs::write(dir.join("data").join("desktop.ini"), "x").unwrap();
+            std::fs::write(dir.join("readme.txt"), "r").unwrap();
+            std::fs::write(dir.join(IGNORE_FILE), ".git/\nreadme.txt\n").unwrap();
+
+            let mut fndb = FNDB::new(":memory:");
+            fndb.connect_rw().unwrap();
+            fndb.init_view().unwrap();
+            ListIgnore::Add("desktop.ini")
+                .execute(fndb.try_conn().unwrap())
+                .unwrap();
+            assert!(
+                ListIgnore::Add("  ")
+                    .execute(fndb.try_conn().unwrap())
+                    .is_err()
+            );
+            assert_eq!(
+                ListIgnore::list(fndb.try_conn().unwrap()).unwrap(),
+                vec!["desktop.ini".to_string()]
+            );
+
+            let info = InfoBase {
+                id: Uuid::new_v4(),
+                name: "忽略规则".to_string(),
+                tag: vec![],
+                description: None,
+                author: None,
+                version: None,
+            };
+            let id = info.id.to_string();
+            ListPack::Insert(dir.to_str().unwrap(), info)
+                .execute(fndb.try_conn().unwrap())
+                .unwrap();
+            let mut paths = ViewPack::GetFileById(&id)
+                .execute(fndb.try_conn().unwrap())
+                .unwrap()
+                .as_file_tree_node()
+                .into_iter()
+                .map(|f| f.path_segments.join("/"))
+                .collect::<Vec<_>>();
+            paths.sort();
+            assert_eq!(paths, vec!["data", "data/a.esp"]);
+
+            let report = VerifyPack::ById(&id)
+                .execute(fndb.try_conn().unwrap())
+                .unwrap();
+            assert!(report[0].is_ok());
+        }
+
+        // 以压缩包为来源插入包，部署时只解压胜出的文件
+        #[test]
+        fn test_archive_pack() {
+            use flate2::{Compression, write::GzEncoder};
+            use std::io::Write;
+            use vfslink_base::Archive;
+
+            let dir = std::env::temp_dir().join("vfslink_archive");
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+
+            // tar.gz 中包含目录条目与 .vfsignore
+            let tar_path = dir.join("pack.tar.gz");
+            let mut builder = tar::Builder::new(GzEncoder::new(
+                std::fs::File::create(&tar_path).unwrap(),
+                Compression::default(),
+            ));
+            for (name, data) in [
+                (IGNORE_FILE, "*.bak\n"),
+                ("data/a.txt", "tar a"),
+                ("data/b.txt", "tar b"),
+                ("data/x.bak", "bak"),
+                ("data/sub/c.txt", "tar c"),
+                // 同一路径再次出现时以最后一次为准
+                ("data/b.txt", "tar b2"),
+            ] {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(data.len() as u64);
+                header.set_mode(0o644);
+                header.set_cksum();
+                builder
+                    .append_data(&mut header, name, data.as_bytes())
+                    .unwrap();
+            }
+            builder.into_inner().unwrap().finish().unwrap();
+
+            // zip 中只有文件条目，上级目录需要补全
+            let zip_path = dir.join("pack.zip");
+            let mut zip = zip::ZipWriter::new(std::fs::File::create(&zip_path).unwrap());
+            zip.start_file("data/a.txt", zip::write::FileOptions::default())
+                .unwrap();
+            zip.write_all(b"zip a").unwrap();
+            zip.finish().unwrap();
+
+            let mut fndb = FNDB::new(":memory:");
+            fndb.connect_rw().unwrap();
+            fndb.init_view().unwrap();
+            let conn = fndb.try_conn().unwrap();
+
+            let info = |name: &str| InfoBase {
+                id: Uuid::new_v4(),
+                name: name.to_string(),
+                tag: vec![],
+                description: None,
+                author: None,
+                version: None,
+            };
+            let tar_info = info("tar");
+            let tar_id = tar_info.id.to_string();
+            ListPack::InsertArchive(tar_path.to_str().unwrap(), tar_info)
+                .execute(conn)
+                .unwrap();
+            let zip_info = info("zip");
+            let zip_id = zip_info.id.to_string();
+            ListPack::InsertArchive(zip_path.to_str().unwrap(), zip_info)
+                .execute(conn)
+                .unwrap();
+            assert!(
+                ListPack::InsertArchive(dir.join("pack.rar").to_str().unwrap(), info("rar"))
+                    .execute(conn)
+                    .is_err()
+            );
+
+            let paths = |id: &str| {
+                let mut paths = ViewPack::GetFileById(id)
+                    .execute(conn)
+                    .unwrap()
+                    .as_file_tree_node()
+                    .into_iter()
+                    .map(|f| f.path_segments.join("/"))
+                    .collect::<Vec<_>>();
+                paths.sort();
+                paths
+            };
+            let tar_paths = vec![
+                "data",
+                "data/a.txt",
+                "data/b.txt",
+                "data/sub",
+                "data/sub/c.txt",
+            ];
+            assert_eq!(paths(&tar_id), tar_paths);
+            assert_eq!(paths(&zip_id), vec!["data", "data/a.txt"]);
+
+            ListPack::SetActive(&tar_id, true).execute(conn).unwrap();
+            ListPack::SetActive(&zip_id, true).execute(conn).unwrap();
+            ListPack::Rescan(&tar_id).execute(conn).unwrap();
+            assert_eq!(paths(&tar_id), tar_paths);
+
+            let target = dir.join("target");
+            let deployer = Deployer::new(target.to_str().unwrap());
+            deployer.deploy(conn).unwrap();
+            let read = |p: &str| std::fs::read_to_string(target.join(p)).unwrap();
+            assert_eq!(read("data/a.txt"), "zip a");
+            assert_eq!(read("data/b.txt"), "tar b2");
+            assert_eq!(read("data/sub/c.txt"), "tar c");
+            let records = deployer.records(conn).unwrap();
+            assert_eq!(records.len(), 3);
+            assert!(records.iter().all(|r| r.method == "copy"));
+            assert!(
+                deployer
+                    .plan_redeploy(conn)
+                    .unwrap()
+                    .actions
+                    .iter()
+                    .all(|a| matches!(a, DeployAction::Keep { .. }))
+            );
+
+            let reports = VerifyPack::All.execute(conn).unwrap();
+            assert!(reports.iter().all(|r| r.is_ok()));
+
+            // 一次读取解压多个文件，不存在的文件返回 NotFound 且不覆盖已有文件
+            let archive = Archive::open(tar_path.to_str().unwrap()).unwrap();
+            let out = dir.join("out");
+            std::fs::create_dir_all(&out).unwrap();
+            let segments = |p: &str| p.split('/').map(String::from).collect::<Vec<_>>();
+            archive
+                .extract_files(&[
+                    (segments("data/b.txt"), out.join("b.txt")),
+                    (segments("data/sub/c.txt"), out.join("c.txt")),
+                ])
+                .unwrap();
+            assert_eq!(
+                std::fs::read_to_string(out.join("b.txt")).unwrap(),
+                "tar b2"
+            );
+            assert_eq!(std::fs::read_to_string(out.join("c.txt")).unwrap(), "tar c");
+            assert!(matches!(
+                archive.extract_files(&[(segments("data/none.txt"), out.join("none.txt"))]),
+                Err(Error::NotFound(_))
+            ));
+            assert!(
+                archive
+                    .extract_file(&segments("data/b.txt"), &out.join("b.txt"))
+                    .is_err()
+            );
+
+            // 压缩包损坏时报告读取错误，而不是所有文件缺失
+            let tar_data = std::fs::read(&tar_path).unwrap();
+            std::fs::write(&tar_path, b"not an archive").unwrap();
+            let report = VerifyPack::ById(&tar_id).execute(conn).unwrap();
+            assert!(report[0].error.is_some() && report[0].missing.is_empty());
+            assert!(!report[0].is_ok());
+            std::fs::write(&tar_path, tar_data).unwrap();
+            assert!(matches!(
+                VerifyPack::ById(&Uuid::new_v4().to_string()).execute(conn),
+                Err(Error::NotFound(_))
+            ));
+
+            // 解压到存储目录后以目录来源入库
+            let storage = dir.join("storage");
+            let import = info("import");
+            let import_dir = storage.join(import.id.to_string());
+            ListPack::ImportArchive(
+                zip_path.to_str().unwrap(),
+                storage.to_str().unwrap(),
+                import,
+            )
+            .execute(conn)
+            .unwrap();
+            assert_eq!(
+                std::fs::read_to_string(import_dir.join("data").join("a.txt")).unwrap(),
+                "zip a"
+            );
+        }
+
+        // 内存来源构造的包，不访问磁盘即可入库与遍历
+        #[test]
+        fn test_memory_source() {
+            use std::sync::Arc;
+
+            let source = Arc::new(
+                MemorySource::new("memory://a")
+                    .with_file(IGNORE_FILE, "*.log\n")
+                    .with_file("data/a.esp", "a")
+                    .with_file("data/sub/b.txt", "b")
+                    .with_file("data/run.log", "log")
+                    .with_dir("empty"),
+            );
+            let mut pack = FNPack::from_source(source.clone(), Uuid::new_v4());
+            pack.walk_dir().unwrap();
+            // .vfsignore 本身与被忽略的 run.log 不入库
+            assert_eq!(pack.children().len(), 5);
+
+            let mut fndb = FNDB::new(":memory:");
+            fndb.connect_rw().unwrap();
+            fndb.init_view().unwrap();
+            let conn = fndb.try_conn().unwrap();
+
+            let info = InfoBase {
+                id: Uuid::new_v4(),
+                name: "内存".to_string(),
+                tag: vec![],
+                description: None,
+                author: None,
+                version: None,
+            };
+            let id = info.id.to_string();
+            ListPack::InsertSource(source.clone(), info)
+                .execute(conn)
+                .unwrap();
+            let mut paths = ViewPack::GetFileById(&id)
+                .execute(conn)
+                .unwrap()
+                .as_file_tree_node()
+                .into_iter()
+                .map(|f| f.path_segments.join("/"))
+                .collect::<Vec<_>>();
+            paths.sort();
+            assert_eq!(
+                paths,
+                vec!["data", "data/a.esp", "data/sub", "data/sub/b.txt", "empty"]
+            );
+
+            // 内存来源不能从数据库中恢复，重新扫描与未指定来源的部署都返回错误
+            let source_kind: String = conn
+                .query_row(
+                    "SELECT source_kind FROM FNPack WHERE id = ?;",
+                    [&id],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(source_kind, "custom");
+            assert!(matches!(
+                vfslink_base::pack_source(conn, &id),
+                Err(Error::InvalidInput(_))
+            ));
+            assert!(matches!(
+                ListPack::Rescan(&id).execute(conn),
+                Err(Error::InvalidInput(_))
+            ));
+            // 无法恢复来源时只在报告中记录错误，指定来源后可以校验
+            let report = VerifyPack::ById(&id).execute(conn).unwrap();
+            assert!(report[0].error.is_some());
+            assert!(!report[0].is_ok());
+            let reports = VerifyPack::All.execute(conn).unwrap();
+            assert!(reports.iter().any(|r| r.pack_id == id && r.error.is_some()));
+            let report = VerifyPack::ByIdWithSource(&id, source.clone())
+                .execute(conn)
+                .unwrap();
+            assert!(report[0].is_ok(), "{:?}", report[0]);
+            ListPack::SetActive(&id, true).execute(conn).unwrap();
+            let target = std::env::temp_dir().join("vfslink_memory_deploy");
+            let _ = std::fs::remove_dir_all(&target);
+            assert!(matches!(
+                Deployer::new(target.to_str().unwrap()).deploy(conn),
+                Err(Error::InvalidInput(_))
+            ));
+            assert!(!target.exists());
+
+            // 部署时指定来源
+            let deployer = Deployer::new(target.to_str().unwrap()).with_source(&id, source);
+            deployer.deploy(conn).unwrap();
+            assert_eq!(
+                std::fs::read_to_string(target.join("data").join("sub").join("b.txt")).unwrap(),
+                "b"
+            );
+            assert!(
+                deployer
+                    .records(conn)
+                    .unwrap()
+                    .iter()
+                    .all(|r| r.method == "copy")
+            );
+        }
+
+        // 批量操作失败时整体回滚
+        #[test]
+        fn test_batch_rollback() {
+            let mut fndb = FNDB::new(PATH);
+            fndb.connect_rw().unwrap();
+
+            let packs = ViewPack::GetPackInfo
+                .execute(fndb.try_conn().unwrap())
+                .unwrap()
+                .as_pack_info();
+            let pack = &packs[0];
+            let missing = Uuid::new_v4().to_string();
+
+            let batch = Batch::new()
+                .pack(ListPack::SetPriority(&pack.id, 1000.5))
+                .pack(ListPack::SetActive(&pack.id, !pack.is_active))
+                .pack(ListPack::Rescan(&missing));
+            assert_eq!(batch.len(), 3);
+            assert!(batch.execute(fndb.try_conn().unwrap()).is_err());
+
+            // 前两项操作被回滚
+            let after = ViewPack::GetPackInfo
+                .execute(fndb.try_conn().unwrap())
+                .unwrap()
+                .as_pack_info();
+            let after = after.iter().find(|p| p.id == pack.id).unwrap();
+            assert_eq!(after.priority, pack.priority);
+            assert_eq!(after.is_active, pack.is_active);
+
+            // 全部成功时提交
+            Batch::new()
+                .pack(ListPack::SetActive(&pack.id, pack.is_active))
+                .tree(ListTree::RemoveCoverRuleByPackId(&missing))
+                .execute(fndb.try_conn().unwrap())
+                .unwrap();
+        }
+
+        // 错误返回
+        #[test]
+        fn test_pack_errors() {
+            let mut fndb = FNDB::new(PATH);
+            fndb.connect_rw().unwrap();
+
+            let id = Uuid::new_v4().to_string();
+            let res = ListPack::Rescan(&id).execute(fndb.try_conn().unwrap());
+            assert!(matches!(res, Err(Error::NotFound(_))));
+
+            let res = ListPack::Rescan("not-a-uuid").execute(fndb.try_conn().unwrap());
+            assert!(matches!(res, Err(Error::NotFound(_))));
+
+            let info = InfoBase {
+                id: Uuid::new_v4(),
+                name: "不存在的包".to_string(),
+                tag: vec![],
+                description: None,
+                author: None,
+                version: None,
+            };
+            let res = ListPack::Insert("./not_exists_pack", info).execute(fndb.try_conn().unwrap());
+            assert!(matches!(res, Err(Error::NotFound(_))));
+
+            let unconnected = FNDB::new(PATH);
+            assert!(matches!(
+                unconnected.try_conn(),
+                Err(Error::InvalidInput(_))
+            ));
+        }
+
         // 整理文件优先级
         #[test]
         fn test_update_file_priority() {
             let mut fndb = FNDB::new(PATH);
-            fndb.connect_rw();
+            fndb.connect_rw().unwrap();
 
             // 查询包列表
             let mut stmt = fndb
-                .get_conn()
+                .try_conn()
+                .unwrap()
                 .prepare(&ViewPack::GetPackInfo.to_sql())
                 .unwrap();
             let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
             print_batches(&rbs).unwrap();
 
-            ListPack::ResetPriority.execute(fndb.get_conn());
+            ListPack::ResetPriority
+                .execute(fndb.try_conn().unwrap())
+                .unwrap();
 
             // 查询包列表
             let mut stmt = fndb
-                .get_conn()
+                .try_conn()
+                .unwrap()
                 .prepare(&ViewPack::GetPackInfo.to_sql())
                 .unwrap();
             let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
@@ -385,23 +1327,32 @@ mod test {
 
     mod tree {
         use super::*;
+        use std::{path::Path, sync::Arc};
+        use vfslink_base::{DirSource, PackSource, model_insert::InfoBase};
 
         // 查询覆盖树
         #[test]
         fn test_get_over_tree() {
             let mut fndb = FNDB::new(PATH);
-            fndb.connect_rw();
+            fndb.connect_rw().unwrap();
 
             // 查询顶层目录
-            let sql = ViewOverTree::GetTop.to_sql();
-            let mut stmt = fndb.get_conn().prepare(&sql).unwrap();
-            let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
+            let view = ViewOverTree::GetTop;
+            let mut stmt = fndb.try_conn().unwrap().prepare(&view.to_sql()).unwrap();
+            let rbs: Vec<RecordBatch> = stmt
+                .query_arrow(params_from_iter(view.params()))
+                .unwrap()
+                .collect();
             print_batches(&rbs).unwrap();
 
             // 查询子项
-            let sql = ViewOverTree::GetChildren(&vec!["音乐".to_string()]).to_sql();
-            let mut stmt = fndb.get_conn().prepare(&sql).unwrap();
-            let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
+            let path = vec!["音乐".to_string()];
+            let view = ViewOverTree::GetChildren(&path);
+            let mut stmt = fndb.try_conn().unwrap().prepare(&view.to_sql()).unwrap();
+            let rbs: Vec<RecordBatch> = stmt
+                .query_arrow(params_from_iter(view.params()))
+                .unwrap()
+                .collect();
             print_batches(&rbs).unwrap();
         }
 
@@ -409,32 +1360,662 @@ mod test {
         #[test]
         fn test_get_all_paths() {
             let mut fndb = FNDB::new(PATH);
-            fndb.connect_rw();
+            fndb.connect_rw().unwrap();
 
             let sql = ViewOverTree::GetAllPaths.to_sql();
-            let mut stmt = fndb.get_conn().prepare(&sql).unwrap();
+            let mut stmt = fndb.try_conn().unwrap().prepare(&sql).unwrap();
             let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
             print_batches(&rbs).unwrap();
 
             let rows = ViewOverTree::GetAllPaths
-                .execute(fndb.get_conn())
+                .execute(fndb.try_conn().unwrap())
                 .unwrap()
                 .as_file_over_link_list();
             println!("{:?}", rows);
         }
 
+        // 依次以包 a、b、c 的来源插入一组启用的包，优先级依次递增
+        fn insert_packs(
+            conn: &duckdb::Connection,
+            source: impl Fn(&str) -> Arc<dyn PackSource>,
+        ) -> Vec<String> {
+            let mut ids = vec![];
+            for (i, name) in ["a", "b", "c"].into_iter().enumerate() {
+                let info = InfoBase {
+                    id: Uuid::new_v4(),
+                    name: name.to_string(),
+                    tag: vec![],
+                    description: None,
+                    author: None,
+                    version: None,
+                };
+                let id = info.id.to_string();
+                ListPack::InsertSource(source(name), info)
+                    .execute(conn)
+                    .unwrap();
+                ListPack::SetActive(&id, true).execute(conn).unwrap();
+                ListPack::SetPriority(&id, i as f64 + 1.0)
+                    .execute(conn)
+                    .unwrap();
+                ids.push(id);
+            }
+            ids
+        }
+
+        // 以内存来源插入一组启用的包，每个文件的内容为包名
+        fn insert_memory_packs(conn: &duckdb::Connection, files: &[&str]) -> Vec<String> {
+            insert_packs(conn, |name| Arc::new(memory_source(name, files)))
+        }
+
+        // 包名对应的内存来源，每个文件的内容为包名
+        fn memory_source(name: &str, files: &[&str]) -> MemorySource {
+            files.iter().fold(
+                MemorySource::new(&format!("memory://{}", name)),
+                |source, path| source.with_file(path, name),
+            )
+        }
+
+        // 为 insert_memory_packs 插入的包构造部署器，内存来源按相同内容重建
+        fn memory_deployer(target: &Path, ids: &[String], files: &[&str]) -> Deployer {
+            ids.iter().zip(["a", "b", "c"]).fold(
+                Deployer::new(target.to_str().unwrap()),
+                |deployer, (id, name)| {
+                    deployer.with_source(id, Arc::new(memory_source(name, files)))
+                },
+            )
+        }
+
+        // 在 root 下为每个包创建目录后插入一组启用的包，每个文件的内容为包名
+        fn insert_dir_packs(conn: &duckdb::Connection, root: &Path, files: &[&str]) -> Vec<String> {
+            insert_packs(conn, |name| {
+                let dir = root.join(name);
+                for path in files {
+                    let file = dir.join(path);
+                    std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+                    std::fs::write(file, name).unwrap();
+                }
+                Arc::new(DirSource::new(dir.to_str().unwrap()))
+            })
+        }
+
+        // 覆盖树中每个文件路径的顶层包id
+        fn winners(conn: &duckdb::Connection) -> std::collections::HashMap<String, String> {
+            ViewOverTree::GetAllPaths
+                .execute(conn)
+                .unwrap()
+                .as_file_over_link_list()
+                .into_iter()
+                .map(|r| (r.path_relative, r.pack_id))
+                .collect()
+        }
+
+        // 模式覆盖规则
+        #[test]
+        fn test_pattern_rule() {
+            use vfslink_base::PatternKind;
+
+            let mut fndb = FNDB::new(":memory:");
+            fndb.connect_rw().unwrap();
+            fndb.init_view().unwrap();
+            let conn = fndb.try_conn().unwrap();
+
+            let ids = insert_memory_packs(
+                conn,
+                &[
+                    "textures/rock/rock.dds",
+                    "textures/sky.dds",
+                    "textures/readme.txt",
+                    "scripts/main.lua",
+                ],
+            );
+            let (a, b) = (&ids[0], &ids[2]);
+            assert!(winners(conn).values().all(|p| p == b));
+
+            // a 接管 textures 目录，b 再取回所有 dds
+            ListTree::AddPatternRule(PatternKind::Prefix, "textures", a)
+                .execute(conn)
+                .unwrap();
+            ListTree::AddPatternRule(PatternKind::Glob, "textures/**/*.dds", b)
+                .execute(conn)
+                .unwrap();
+            let res = winners(conn);
+            assert_eq!(&res["textures/rock/rock.dds"], b);
+            assert_eq!(&res["textures/sky.dds"], b);
+            assert_eq!(&res["textures/readme.txt"], a);
+            assert_eq!(&res["scripts/main.lua"], b);
+
+            // 调整顺序后前缀规则优先
+            let rules = ListTree::pattern_rules(conn).unwrap();
+            assert_eq!(rules.len(), 2);
+            assert_eq!(rules[0].kind, PatternKind::Glob.as_str());
+            let prefix = &rules[1];
+            ListTree::SetPatternRuleOrder(prefix.id, rules[0].rule_order + 1.0)
+                .execute(conn)
+                .unwrap();
+            assert_eq!(&winners(conn)["textures/sky.dds"], a);
+
+            // 停用后恢复
+            ListTree::SetPatternRuleActive(prefix.id, false)
+                .execute(conn)
+                .unwrap();
+            assert_eq!(&winners(conn)["textures/readme.txt"], b);
+
+            assert!(matches!(
+                ListTree::AddPatternRule(PatternKind::Glob, "/", a).execute(conn),
+                Err(Error::InvalidInput(_))
+            ));
+
+            // 删除包时一并删除规则
+            ListPack::RemoveById(a).execute(conn).unwrap();
+            let rules = ListTree::pattern_rules(conn).unwrap();
+            assert!(rules.iter().all(|r| &r.pack_id == b));
+        }
+
+        // 停用文件与排除规则
+        #[test]
+        fn test_exclude_rule() {
+            use vfslink_base::PatternKind;
+
+            let mut fndb = FNDB::new(":memory:");
+            fndb.connect_rw().unwrap();
+            fndb.init_view().unwrap();
+            let conn = fndb.try_conn().unwrap();
+
+            let ids = insert_memory_packs(
+                conn,
+                &["config/foo.ini", "config/bar.ini", "logs/a.log", "main.esp"],
+            );
+            let file_id = |pack_id: &str, path: &str| {
+                ViewPack::GetFileById(pack_id)
+                    .execute(conn)
+                    .unwrap()
+                    .as_file_tree_node()
+                    .into_iter()
+                    .find(|f| f.path_segments.join("/") == path)
+                    .unwrap()
+                    .id
+            };
+
+            // 停用的文件由下一个包接管，全部停用时不部署
+            let top = file_id(&ids[2], "config/foo.ini");
+            ListTree::SetActive(&top, false).execute(conn).unwrap();
+            assert_eq!(winners(conn)["config/foo.ini"], ids[1]);
+            for pack_id in &ids[..2] {
+                let id = file_id(pack_id, "config/foo.ini");
+                ListTree::SetActive(&id, false).execute(conn).unwrap();
+            }
+            assert!(!winners(conn).contains_key("config/foo.ini"));
+            ListTree::SetActive(&top, true).execute(conn).unwrap();
+            assert_eq!(winners(conn)["config/foo.ini"], ids[2]);
+
+            // 排除规则不论由哪些包提供都移除路径
+            ListTree::AddExcludeRule(PatternKind::Exact, "config/foo.ini")
+                .execute(conn)
+                .unwrap();
+            ListTree::AddExcludeRule(PatternKind::Glob, "**/*.log")
+                .execute(conn)
+                .unwrap();
+            let res = winners(conn);
+            assert!(!res.contains_key("config/foo.ini"));
+            assert!(!res.contains_key("logs/a.log"));
+            assert!(res.contains_key("config/bar.ini"));
+            assert!(res.contains_key("main.esp"));
+
+            // 排除规则优先于覆盖规则
+            ListTree::AddPatternRule(PatternKind::Prefix, "config", &ids[0])
+                .execute(conn)
+                .unwrap();
+            let res = winners(conn);
+            assert!(!res.contains_key("config/foo.ini"));
+            assert_eq!(res["config/bar.ini"], ids[0]);
+
+            let rules = ListTree::exclude_rules(conn).unwrap();
+            assert_eq!(rules.len(), 2);
+            ListTree::SetExcludeRuleActive(rules[0].id, false)
+                .execute(conn)
+                .unwrap();
+            // a 中的 config/foo.ini 已停用，覆盖规则落空后按包优先级
+            assert_eq!(winners(conn)["config/foo.ini"], ids[2]);
+            ListTree::RemoveExcludeRule(rules[1].id)
+                .execute(conn)
+                .unwrap();
+            assert!(winners(conn).contains_key("logs/a.log"));
+        }
+
+        // 配置方案
+        #[test]
+        fn test_profile() {
+            use vfslink_base::ListProfile;
+
+            let mut fndb = FNDB::new(":memory:");
+            fndb.connect_rw().unwrap();
+            fndb.init_view().unwrap();
+            let conn = fndb.try_conn().unwrap();
+
+            let ids = insert_memory_packs(conn, &["main.esp", "config.ini"]);
+            assert_eq!(ListProfile::current(conn).unwrap().name, "default");
+            assert_eq!(winners(conn)["main.esp"], ids[2]);
+
+            // 复制的方案与原方案互不影响
+            ListProfile::CloneFrom("default", "alt")
+                .execute(conn)
+                .unwrap();
+            ListProfile::Switch("alt").execute(conn).unwrap();
+            assert_eq!(winners(conn)["main.esp"], ids[2]);
+            ListPack::SetActive(&ids[2], false).execute(conn).unwrap();
+            let config = ViewPack::GetFileById(&ids[1])
+                .execute(conn)
+                .unwrap()
+                .as_file_tree_node()
+                .into_iter()
+                .find(|f| f.name == "config.ini")
+                .unwrap();
+            ListTree::SetActive(&config.id, false)
+                .execute(conn)
+                .unwrap();
+            let res = winners(conn);
+            assert_eq!(res["main.esp"], ids[1]);
+            assert_eq!(res["config.ini"], ids[0]);
+            let packs = ViewPack::GetPackInfo.execute(conn).unwrap().as_pack_info();
+            assert!(!packs.iter().find(|p| p.id == ids[2]).unwrap().is_active);
+
+            ListProfile::Switch("default").execute(conn).unwrap();
+            let res = winners(conn);
+            assert_eq!(res["main.esp"], ids[2]);
+            assert_eq!(res["config.ini"], ids[2]);
+            let packs = ViewPack::GetPackInfo.execute(conn).unwrap().as_pack_info();
+            assert!(packs.iter().all(|p| p.is_active));
+
+            // 新方案中所有包都不启用
+            ListProfile::Create("empty").execute(conn).unwrap();
+            ListProfile::Switch("empty").execute(conn).unwrap();
+            assert!(winners(conn).is_empty());
+            assert_eq!(
+                ViewPack::GetPackInfo
+                    .execute(conn)
+                    .unwrap()
+                    .as_pack_info()
+                    .len(),
+                3
+            );
+
+            // 部署目标
+            assert!(matches!(
+                Deployer::from_profile(conn),
+                Err(Error::InvalidInput(_))
+            ));
+            let target = std::env::temp_dir().join("vfslink_profile_deploy");
+            ListProfile::SetTarget("empty", target.to_str().unwrap())
+                .execute(conn)
+                .unwrap();
+            Deployer::from_profile(conn).unwrap();
+
+            assert!(matches!(
+                ListProfile::Create("alt").execute(conn),
+                Err(Error::InvalidInput(_))
+            ));
+            assert!(matches!(
+                ListProfile::Switch("missing").execute(conn),
+                Err(Error::NotFound(_))
+            ));
+            assert!(matches!(
+                ListProfile::Remove("empty").execute(conn),
+                Err(Error::InvalidInput(_))
+            ));
+            ListProfile::Remove("alt").execute(conn).unwrap();
+            let names = ListProfile::list(conn)
+                .unwrap()
+                .into_iter()
+                .map(|p| p.name)
+                .collect::<Vec<_>>();
+            assert_eq!(names, vec!["default", "empty"]);
+        }
+
+        // 部署状态只按本目标目录的记录同步，包的部署状态只写入当前方案
+        #[test]
+        fn test_profile_deploy_state() {
+            use vfslink_base::ListProfile;
+
+            let mut fndb = FNDB::new(":memory:");
+            fndb.connect_rw().unwrap();
+            fndb.init_view().unwrap();
+            let conn = fndb.try_conn().unwrap();
+
+            let files = ["main.esp"];
+            let ids = insert_memory_packs(conn, &files);
+            let deployed_packs = |conn: &duckdb::Connection| {
+                let mut packs = ViewPack::GetPackInfo
+                    .execute(conn)
+                    .unwrap()
+                    .as_pack_info()
+                    .into_iter()
+                    .filter(|p| p.is_deployed)
+                    .map(|p| p.id)
+                    .collect::<Vec<_>>();
+                packs.sort();
+                packs
+            };
+            let deployed_files = |conn: &duckdb::Connection, id: &str| {
+                ViewPack::GetFileById(id)
+                    .execute(conn)
+                    .unwrap()
+                    .as_file_tree_node()
+                    .into_iter()
+                    .filter(|f| f.is_deployed)
+                    .count()
+            };
+
+            // 默认方案部署 c 到第一个目标目录
+            let root = std::env::temp_dir().join("vfslink_profile_state");
+            let _ = std::fs::remove_dir_all(&root);
+            let first = memory_deployer(&root.join("first"), &ids, &files);
+            first.deploy(conn).unwrap();
+            assert_eq!(deployed_packs(conn), vec![ids[2].clone()]);
+            assert_eq!(deployed_files(conn, &ids[2]), 1);
+
+            // 复制的方案没有部署过
+            ListProfile::CloneFrom("default", "copy")
+                .execute(conn)
+                .unwrap();
+            ListProfile::Switch("copy").execute(conn).unwrap();
+            assert!(deployed_packs(conn).is_empty());
+            ListProfile::Switch("default").execute(conn).unwrap();
+
+            // 另一个方案只启用 a 并部署到第二个目标目录
+            ListProfile::Create("other").execute(conn).unwrap();
+            ListProfile::Switch("other").execute(conn).unwrap();
+            ListPack::SetActive(&ids[0], true).execute(conn).unwrap();
+            let second = memory_deployer(&root.join("second"), &ids, &files);
+            second.deploy(conn).unwrap();
+            assert_eq!(deployed_packs(conn), vec![ids[0].clone()]);
+            assert_eq!(deployed_files(conn, &ids[0]), 1);
+            assert_eq!(deployed_files(conn, &ids[2]), 1);
+
+            // 撤销第二个目标目录的部署，不影响第一个目标目录与默认方案
+            second.undeploy(conn).unwrap();
+            assert!(deployed_packs(conn).is_empty());
+            assert_eq!(deployed_files(conn, &ids[0]), 0);
+            assert_eq!(deployed_files(conn, &ids[2]), 1);
+            ListProfile::Switch("default").execute(conn).unwrap();
+            assert_eq!(deployed_packs(conn), vec![ids[2].clone()]);
+
+            first.undeploy(conn).unwrap();
+            assert!(deployed_packs(conn).is_empty());
+            assert_eq!(deployed_files(conn, &ids[2]), 0);
+        }
+
+        // 模式覆盖规则与排除规则按方案记录
+        #[test]
+        fn test_profile_rules() {
+            use vfslink_base::{ListProfile, PatternKind};
+
+            let mut fndb = FNDB::new(":memory:");
+            fndb.connect_rw().unwrap();
+            fndb.init_view().unwrap();
+            let conn = fndb.try_conn().unwrap();
+
+            let ids = insert_memory_packs(conn, &["textures/sky.dds", "config.ini", "run.log"]);
+            let (a, c) = (&ids[0], &ids[2]);
+            ListProfile::Create("other").execute(conn).unwrap();
+            ListProfile::Switch("other").execute(conn).unwrap();
+            for id in &ids {
+                ListPack::SetActive(id, true).execute(conn).unwrap();
+            }
+
+            // 默认方案：a 接管 textures，排除日志
+            ListProfile::Switch("default").execute(conn).unwrap();
+            ListTree::AddPatternRule(PatternKind::Prefix, "textures", a)
+                .execute(conn)
+                .unwrap();
+            ListTree::AddExcludeRule(PatternKind::Glob, "*.log")
+                .execute(conn)
+                .unwrap();
+            let default_pattern = ListTree::pattern_rules(conn).unwrap()[0].id;
+            let default_exclude = ListTree::exclude_rules(conn).unwrap()[0].id;
+
+            // 另一个方案：排除 config.ini，没有模式覆盖规则
+            ListProfile::Switch("other").execute(conn).unwrap();
+            ListTree::AddExcludeRule(PatternKind::Exact, "config.ini")
+                .execute(conn)
+                .unwrap();
+            assert!(ListTree::pattern_rules(conn).unwrap().is_empty());
+            let excludes = ListTree::exclude_rules(conn).unwrap();
+            assert_eq!(excludes.len(), 1);
+            assert_eq!(excludes[0].pattern, "config.ini");
+            let res = winners(conn);
+            assert_eq!(&res["textures/sky.dds"], c);
+            assert_eq!(&res["run.log"], c);
+            assert!(!res.contains_key("config.ini"));
+
+            // 不能操作其他方案的规则
+            ListTree::SetPatternRuleActive(default_pattern, false)
+                .execute(conn)
+                .unwrap();
+            ListTree::RemoveExcludeRule(default_exclude)
+                .execute(conn)
+                .unwrap();
+
+            ListProfile::Switch("default").execute(conn).unwrap();
+            let patterns = ListTree::pattern_rules(conn).unwrap();
+            assert_eq!(patterns.len(), 1);
+            assert!(patterns[0].is_active);
+            let excludes = ListTree::exclude_rules(conn).unwrap();
+            assert_eq!(excludes.len(), 1);
+            assert_eq!(excludes[0].pattern, "*.log");
+            let res = winners(conn);
+            assert_eq!(&res["textures/sky.dds"], a);
+            assert_eq!(&res["config.ini"], c);
+            assert!(!res.contains_key("run.log"));
+
+            // 复制方案时一并复制规则，删除方案时一并删除
+            ListProfile::CloneFrom("default", "copy")
+                .execute(conn)
+                .unwrap();
+            ListProfile::Switch("copy").execute(conn).unwrap();
+            assert_eq!(ListTree::pattern_rules(conn).unwrap().len(), 1);
+            assert_eq!(ListTree::exclude_rules(conn).unwrap().len(), 1);
+            assert_eq!(&winners(conn)["textures/sky.dds"], a);
+            ListProfile::Switch("default").execute(conn).unwrap();
+            ListProfile::Remove("copy").execute(conn).unwrap();
+            ListProfile::Remove("other").execute(conn).unwrap();
+            let count = |table: &str| -> i64 {
+                conn.query_row(&format!("SELECT count(*) FROM {};", table), [], |row| {
+                    row.get(0)
+                })
+                .unwrap()
+            };
+            assert_eq!(count("PriorityRule"), 1);
+            assert_eq!(count("ExcludeRule"), 1);
+        }
+
+        // 包关系校验
+        #[test]
+        fn test_pack_relation() {
+            use vfslink_base::{ListRelation, RelationIssue, RelationKind};
+
+            let mut fndb = FNDB::new(":memory:");
+            fndb.connect_rw().unwrap();
+            fndb.init_view().unwrap();
+            let conn = fndb.try_conn().unwrap();
+
+            // 优先级 a < b < c
+            let ids = insert_memory_packs(conn, &["main.esp"]);
+            let (a, b, c) = (&ids[0], &ids[1], &ids[2]);
+            let missing = Uuid::new_v4().to_string();
+
+            ListRelation::Add(c, RelationKind::Requires, a, Some(">=1.2"))
+                .execute(conn)
+                .unwrap();
+            ListRelation::Add(c, RelationKind::Requires, &missing, None)
+                .execute(conn)
+                .unwrap();
+            ListRelation::Add(a, RelationKind::LoadAfter, b, None)
+                .execute(conn)
+                .unwrap();
+            ListRelation::Add(b, RelationKind::Incompatible, c, None)
+                .execute(conn)
+                .unwrap();
+            assert_eq!(ListRelation::list(conn, c).unwrap().len(), 2);
+
+            let issues = ListRelation::validate(conn).unwrap();
+            assert_eq!(issues.len(), 4);
+            assert!(issues.contains(&RelationIssue::VersionMismatch {
+                pack_id: c.clone(),
+                target_id: a.clone(),
+                version_req: ">=1.2".to_string(),
+                found: None,
+            }));
+            assert!(issues.contains(&RelationIssue::MissingDependency {
+                pack_id: c.clone(),
+                target_id: missing.clone(),
+                installed: false,
+            }));
+            assert!(issues.contains(&RelationIssue::OrderViolation {
+                pack_id: a.clone(),
+                target_id: b.clone(),
+            }));
+            assert!(issues.contains(&RelationIssue::Incompatible {
+                pack_id: b.clone(),
+                target_id: c.clone(),
+            }));
+
+            // 满足版本要求、调整顺序、移除依赖后只剩不兼容
+            let info = InfoBase {
+                id: a.parse().unwrap(),
+                name: "a".to_string(),
+                tag: vec![],
+                description: None,
+                author: None,
+                version: Some("v1.10".to_string()),
+            };
+            ListPack::SetInfo(a, info).execute(conn).unwrap();
+            ListPack::SetPriority(a, 2.5).execute(conn).unwrap();
+            ListRelation::Remove(c, RelationKind::Requires, &missing)
+                .execute(conn)
+                .unwrap();
+            assert_eq!(
+                ListRelation::validate(conn).unwrap(),
+                vec![RelationIssue::Incompatible {
+                    pack_id: b.clone(),
+                    target_id: c.clone(),
+                }]
+            );
+
+            // 停用 c 后其声明的关系与针对它的不兼容都不再报告
+            ListPack::SetActive(c, false).execute(conn).unwrap();
+            assert!(ListRelation::validate(conn).unwrap().is_empty());
+
+            assert!(matches!(
+                ListRelation::Add(a, RelationKind::LoadAfter, b, Some(">=1")).execute(conn),
+                Err(Error::InvalidInput(_))
+            ));
+            assert!(matches!(
+                ListRelation::Add(a, RelationKind::Requires, b, Some(">= ")).execute(conn),
+                Err(Error::InvalidInput(_))
+            ));
+
+            // 删除包时一并删除其声明的关系
+            ListPack::RemoveById(b).execute(conn).unwrap();
+            assert!(ListRelation::list(conn, b).unwrap().is_empty());
+        }
+
+        // 按包关系自动排序
+        #[test]
+        fn test_auto_sort() {
+            use vfslink_base::{ListRelation, RelationKind};
+
+            let mut fndb = FNDB::new(":memory:");
+            fndb.connect_rw().unwrap();
+            fndb.init_view().unwrap();
+            let conn = fndb.try_conn().unwrap();
+
+            let ids = insert_memory_packs(conn, &["main.esp"]);
+            let (a, b, c) = (&ids[0], &ids[1], &ids[2]);
+            let load_order = || {
+                ViewPack::GetPackInfo
+                    .execute(conn)
+                    .unwrap()
+                    .as_pack_info()
+                    .into_iter()
+                    .map(|p| p.id)
+                    .collect::<Vec<_>>()
+            };
+
+            // 没有约束时保持原顺序
+            let res = ListRelation::auto_sort(conn).unwrap();
+            assert!(!res.changed);
+            assert_eq!(&res.order, &ids);
+
+            // a 排在 c 之后，c 排在 b 之前
+            ListRelation::Add(a, RelationKind::LoadAfter, c, None)
+                .execute(conn)
+                .unwrap();
+            ListRelation::Add(c, RelationKind::LoadBefore, b, None)
+                .execute(conn)
+                .unwrap();
+            assert_eq!(ListRelation::validate(conn).unwrap().len(), 2);
+            let res = ListRelation::auto_sort(conn).unwrap();
+            assert!(res.changed && res.cycles.is_empty());
+            assert_eq!(res.order, vec![c.clone(), a.clone(), b.clone()]);
+            assert_eq!(load_order(), res.order);
+            assert!(ListRelation::validate(conn).unwrap().is_empty());
+
+            // 排序后添加的包取到不冲突的优先级，排在最后
+            let info = InfoBase {
+                id: Uuid::new_v4(),
+                name: "d".to_string(),
+                tag: vec![],
+                description: None,
+                author: None,
+                version: None,
+            };
+            let d = info.id.to_string();
+            ListPack::InsertSource(Arc::new(memory_source("d", &["main.esp"])), info)
+                .execute(conn)
+                .unwrap();
+            assert_eq!(
+                load_order(),
+                vec![c.clone(), a.clone(), b.clone(), d.clone()]
+            );
+            ListPack::RemoveById(&d).execute(conn).unwrap();
+
+            // 循环时不修改顺序
+            ListRelation::Add(b, RelationKind::Requires, a, None)
+                .execute(conn)
+                .unwrap();
+            ListRelation::Add(a, RelationKind::LoadAfter, b, None)
+                .execute(conn)
+                .unwrap();
+            let res = ListRelation::auto_sort(conn).unwrap();
+            assert!(!res.changed && res.order.is_empty());
+            assert_eq!(res.cycles.len(), 1);
+            let mut cycle = res.cycles[0].clone();
+            cycle.sort();
+            let mut expected = vec![a.clone(), b.clone()];
+            expected.sort();
+            assert_eq!(cycle, expected);
+            assert_eq!(load_order(), vec![c.clone(), a.clone(), b.clone()]);
+        }
+
         // 查询冲突文件
         #[test]
         fn test_get_conflict_files() {
             let mut fndb = FNDB::new(PATH);
-            fndb.connect_rw();
+            fndb.connect_rw().unwrap();
 
-            let sql = ViewOverTree::GetConflictFiles("说明.txt").to_sql();
-            let mut stmt = fndb.get_conn().prepare(&sql).unwrap();
-            let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
+            let view = ViewOverTree::GetConflictFiles("说明.txt");
+            let mut stmt = fndb.try_conn().unwrap().prepare(&view.to_sql()).unwrap();
+            let rbs: Vec<RecordBatch> = stmt
+                .query_arrow(params_from_iter(view.params()))
+                .unwrap()
+                .collect();
             print_batches(&rbs).unwrap();
 
-            let t = vfslink_base::model_select::ConflictFileList::get_res(&mut stmt).unwrap();
+            let t = vfslink_base::model_select::ConflictFileList::get_res(
+                &mut stmt,
+                params_from_iter(view.params()),
+            )
+            .unwrap();
 
             println!("{:#?}", t);
         }
@@ -443,10 +2024,10 @@ mod test {
         #[test]
         fn test_add_conflict_rule() {
             let mut fndb = FNDB::new(PATH);
-            fndb.connect_rw();
+            fndb.connect_rw().unwrap();
 
             let res = ViewOverTree::GetConflictFiles("说明.txt")
-                .execute(fndb.get_conn())
+                .execute(fndb.try_conn().unwrap())
                 .unwrap();
             println!("{:#?}", res);
 
@@ -456,17 +2037,21 @@ mod test {
             let fid = &t[0].files[1].file_id;
             let pid = &t[0].files[1].pack_id;
 
-            ListTree::RemoveCoverRule(path).execute(fndb.get_conn());
+            ListTree::RemoveCoverRule(path)
+                .execute(fndb.try_conn().unwrap())
+                .unwrap();
 
             let res = ViewOverTree::GetConflictFiles("说明.txt")
-                .execute(fndb.get_conn())
+                .execute(fndb.try_conn().unwrap())
                 .unwrap();
             println!("{:#?}", res);
 
-            ListTree::AddCoverRule(path, fid, pid).execute(fndb.get_conn());
+            ListTree::AddCoverRule(path, fid, pid)
+                .execute(fndb.try_conn().unwrap())
+                .unwrap();
 
             let res = ViewOverTree::GetConflictFiles("说明.txt")
-                .execute(fndb.get_conn())
+                .execute(fndb.try_conn().unwrap())
                 .unwrap();
             println!("{:#?}", res);
         }
@@ -475,10 +2060,10 @@ mod test {
         #[test]
         fn test_check_hash_conflict() {
             let mut fndb = FNDB::new(PATH);
-            fndb.connect_rw();
+            fndb.connect_rw().unwrap();
 
             let res = ViewOverTree::GetHashEqualFiles("0cc6ab58-79a2-47ae-b253-66bbeeaf8f38")
-                .execute(fndb.get_conn())
+                .execute(fndb.try_conn().unwrap())
                 .unwrap()
                 .as_hash_equal_file_list();
             println!("{:#?}", res);
@@ -488,24 +2073,459 @@ mod test {
         #[test]
         fn test_get_all_hash_conflict_files() {
             let mut fndb = FNDB::new(PATH);
-            fndb.connect_rw();
+            fndb.connect_rw().unwrap();
 
             let sql = ViewOverTree::GetAllHashEqualFiles.to_sql();
-            let mut stmt = fndb.get_conn().prepare(&sql).unwrap();
+            let mut stmt = fndb.try_conn().unwrap().prepare(&sql).unwrap();
             let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
             print_batches(&rbs).unwrap();
 
             let res = ViewOverTree::GetAllHashEqualFiles
-                .execute(fndb.get_conn())
+                .execute(fndb.try_conn().unwrap())
+                .unwrap()
+                .as_hash_equal_file_group();
+            println!("{:#?}", res);
+        }
+
+        // 获取所有互为硬链接的文件
+        #[test]
+        fn test_get_all_hardlink_files() {
+            let mut fndb = FNDB::new(PATH);
+            fndb.connect_rw().unwrap();
+
+            let sql = ViewOverTree::GetAllHardlinkFiles.to_sql();
+            let mut stmt = fndb.try_conn().unwrap().prepare(&sql).unwrap();
+            let rbs: Vec<RecordBatch> = stmt.query_arrow([]).unwrap().collect();
+            print_batches(&rbs).unwrap();
+
+            let res = ViewOverTree::GetAllHardlinkFiles
+                .execute(fndb.try_conn().unwrap())
                 .unwrap()
                 .as_hash_equal_file_group();
             println!("{:#?}", res);
         }
 
-        //TODO：部署未部署的文件
+        // 部署所有覆盖树顶层文件
+        #[test]
+        fn test_deploy() {
+            let mut fndb = FNDB::new(":memory:");
+            fndb.connect_rw().unwrap();
+            fndb.init_view().unwrap();
+            let conn = fndb.try_conn().unwrap();
+
+            let root = std::env::temp_dir().join("vfslink_deploy");
+            let _ = std::fs::remove_dir_all(&root);
+            let packs = root.join("packs");
+            let ids = insert_dir_packs(conn, &packs, &["main.esp", "data/a.txt"]);
+
+            let target = root.join("target");
+            let deployer = Deployer::new(target.to_str().unwrap());
+            let res = deployer.deploy(conn).unwrap();
+            assert_eq!(res.created_dirs, vec!["data".to_string()]);
+            let mut deployed = res.deployed.clone();
+            deployed.sort();
+            assert_eq!(deployed, vec!["data/a.txt", "main.esp"]);
+
+            // 部署的文件为指向顶层包中文件的符号链接
+            for path in ["main.esp", "data/a.txt"] {
+                let dst = target.join(path);
+                assert_eq!(
+                    std::fs::read_link(&dst).unwrap(),
+                    packs.join("c").join(path)
+                );
+                assert_eq!(std::fs::read_to_string(&dst).unwrap(), "c");
+            }
+
+            // 再次部署时不应重复创建
+            let res = deployer.deploy(conn).unwrap();
+            assert!(res.created_dirs.is_empty());
+            assert_eq!(res.deployed.len(), 2);
+
+            // 只有提供顶层文件的包处于部署状态
+            let packs = ViewPack::GetPackInfo.execute(conn).unwrap().as_pack_info();
+            for p in packs {
+                assert_eq!(p.is_deployed, p.id == ids[2]);
+            }
+        }
+
+        // 以硬链接方式部署，写入部署的文件视为部署后被修改
+        #[test]
+        fn test_deploy_hardlink() {
+            let mut fndb = FNDB::new(":memory:");
+            fndb.connect_rw().unwrap();
+            fndb.init_view().unwrap();
+            let conn = fndb.try_conn().unwrap();
+
+            let root = std::env::temp_dir().join("vfslink_deploy_hardlink");
+            let _ = std::fs::remove_dir_all(&root);
+            let packs = root.join("packs");
+            insert_dir_packs(conn, &packs, &["main.esp", "data/a.txt"]);
+
+            let target = root.join("target");
+            let deployer =
+                Deployer::new(target.to_str().unwrap()).with_method(DeployMethod::Hardlink);
+            let res = deployer.deploy(conn).unwrap();
+            assert_eq!(res.deployed.len(), 2);
+
+            // 目标目录与包在同一文件系统，不会退化为复制
+            let records = deployer.records(conn).unwrap();
+            assert_eq!(records.len(), 2);
+            assert!(records.iter().all(|r| r.method == "hardlink"));
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                for path in ["main.esp", "data/a.txt"] {
+                    let dst = std::fs::metadata(target.join(path)).unwrap();
+                    let src = std::fs::metadata(packs.join("c").join(path)).unwrap();
+                    assert_eq!((dst.dev(), dst.ino()), (src.dev(), src.ino()));
+                }
+            }
+            assert!(
+                deployer
+                    .plan_redeploy(conn)
+                    .unwrap()
+                    .actions
+                    .iter()
+                    .all(|a| matches!(a, DeployAction::Keep { .. }))
+            );
+
+            // 通过硬链接写入同时修改了包中的文件，撤销部署时保留
+            std::fs::write(target.join("main.esp"), "edited").unwrap();
+            let res = deployer.undeploy(conn).unwrap();
+            assert_eq!(res.modified, vec!["main.esp".to_string()]);
+            assert_eq!(res.removed, vec!["data/a.txt".to_string()]);
+            assert_eq!(
+                std::fs::read_to_string(target.join("main.esp")).unwrap(),
+                "edited"
+            );
+            assert!(!target.join("data").exists());
+        }
+
+        // 撤销部署只删除未被修改的部署文件与已变空的新建目录
+        #[test]
+        fn test_undeploy() {
+            let mut fndb = FNDB::new(":memory:");
+            fndb.connect_rw().unwrap();
+            fndb.init_view().unwrap();
+            let conn = fndb.try_conn().unwrap();
+
+            let files = ["main.esp", "data/a.txt", "saves/slot.txt", "textures/t.dds"];
+            let ids = insert_memory_packs(conn, &files);
+            let target = std::env::temp_dir().join("vfslink_undeploy");
+            let _ = std::fs::remove_dir_all(&target);
+            let deployer = memory_deployer(&target, &ids, &files);
+            assert_eq!(deployer.deploy(conn).unwrap().deployed.len(), 4);
+
+            // 用户修改了部署的文件，并在新建的目录中放入自己的文件
+            std::fs::write(target.join("data/a.txt"), "edited").unwrap();
+            std::fs::write(target.join("saves/user.sav"), "user").unwrap();
+
+            let mut res = deployer.undeploy(conn).unwrap();
+            res.removed.sort();
+            assert_eq!(res.modified, vec!["data/a.txt".to_string()]);
+            assert_eq!(
+                res.removed,
+                vec!["main.esp", "saves/slot.txt", "textures/t.dds"]
+            );
+            assert_eq!(res.removed_dirs, vec!["textures".to_string()]);
+
+            // 被修改的文件与含有其他文件的目录保留在磁盘上
+            let read = |p: &str| std::fs::read_to_string(target.join(p)).unwrap();
+            assert_eq!(read("data/a.txt"), "edited");
+            assert_eq!(read("saves/user.sav"), "user");
+            for path in ["main.esp", "saves/slot.txt", "textures"] {
+                assert!(!target.join(path).exists());
+            }
+
+            assert!(deployer.records(conn).unwrap().is_empty());
+            let packs = ViewPack::GetPackInfo.execute(conn).unwrap().as_pack_info();
+            assert!(packs.iter().all(|p| !p.is_deployed));
+        }
+
+        // 生成部署计划并序列化，确认后再执行
+        #[test]
+        fn test_deploy_plan() {
+            let mut fndb = FNDB::new(":memory:");
+            fndb.connect_rw().unwrap();
+            fndb.init_view().unwrap();
+            let conn = fndb.try_conn().unwrap();
+
+            let files = ["main.esp", "data/a.txt"];
+            let ids = insert_memory_packs(conn, &files);
+            let root = std::env::temp_dir().join("vfslink_deploy_plan");
+            let _ = std::fs::remove_dir_all(&root);
+            let target = root.join("target");
+            let deployer = memory_deployer(&target, &ids, &files);
+
+            // 计划经前端序列化往返
+            let plan = deployer.plan_redeploy(conn).unwrap();
+            let json = serde_json::to_string_pretty(&plan).unwrap();
+            let plan: vfslink_base::DeployPlan = serde_json::from_str(&json).unwrap();
+
+            // 生成计划不应修改磁盘
+            assert!(deployer.records(conn).unwrap().is_empty());
+            assert!(!target.exists());
+
+            // 越出目标目录的路径与来源被篡改的文件使整个计划被拒绝
+            let forge = |f: &dyn Fn(&mut vfslink_base::PlannedFile)| {
+                let mut plan = plan.clone();
+                for action in &mut plan.actions {
+                    if let DeployAction::Place(file) = action {
+                        f(file);
+                    }
+                }
+                plan
+            };
+            for forged in [
+                forge(&|file| file.path = format!("../{}", file.path)),
+                forge(&|file| file.path = format!("/{}", file.path)),
+                forge(&|file| file.source = root.join("other").to_string_lossy().to_string()),
+                forge(&|file| file.pack_id = ids[0].clone()),
+            ] {
+                assert!(matches!(
+                    deployer.apply(conn, &forged),
+                    Err(Error::InvalidInput(_))
+                ));
+            }
+            assert!(!root.join("main.esp").exists());
+            assert!(deployer.records(conn).unwrap().is_empty());
+
+            let res = deployer.apply(conn, &plan).unwrap();
+            res.added
+                .iter()
+                .for_each(|p| assert!(target.join(p).is_file()));
+            assert_eq!(res.added.len(), 2);
+
+            // 执行后再次生成的计划只包含无变化的文件
+            let plan = deployer.plan_redeploy(conn).unwrap();
+            assert!(
+                plan.actions
+                    .iter()
+                    .all(|a| matches!(a, DeployAction::Keep { .. }))
+            );
+
+            // 不能删除用户的目录，也不能备份之后没有部署文件的路径
+            std::fs::create_dir_all(target.join("user")).unwrap();
+            std::fs::write(target.join("user.sav"), "user").unwrap();
+            for action in [
+                DeployAction::RemoveDir {
+                    path: "user".to_string(),
+                },
+                DeployAction::Backup {
+                    path: "user.sav".to_string(),
+                },
+            ] {
+                let mut forged = plan.clone();
+                forged.actions = vec![action];
+                assert!(matches!(
+                    deployer.apply(conn, &forged),
+                    Err(Error::InvalidInput(_))
+                ));
+            }
+            assert!(target.join("user").is_dir());
+            assert_eq!(
+                std::fs::read_to_string(target.join("user.sav")).unwrap(),
+                "user"
+            );
+            deployer.undeploy(conn).unwrap();
+        }
+
+        // 部署时备份目标目录中已存在的原始文件，撤销部署后恢复
+        #[test]
+        fn test_deploy_backup() {
+            let mut fndb = FNDB::new(":memory:");
+            fndb.connect_rw().unwrap();
+            fndb.init_view().unwrap();
+            let conn = fndb.try_conn().unwrap();
+
+            let files = ["main.esp", "data/a.txt"];
+            let ids = insert_memory_packs(conn, &files);
+            let target = std::env::temp_dir().join("vfslink_deploy_backup");
+            let backup = std::env::temp_dir().join("vfslink_deploy_backup.vfsbackup");
+            let _ = std::fs::remove_dir_all(&target);
+            let _ = std::fs::remove_dir_all(&backup);
+            let deployer = memory_deployer(&target, &ids, &files);
+
+            // 在待部署的位置放入原始文件
+            let vanilla = target.join("main.esp");
+            std::fs::create_dir_all(&target).unwrap();
+            std::fs::write(&vanilla, "vanilla").unwrap();
+
+            let res = deployer.deploy(conn).unwrap();
+            assert_eq!(res.backed_up, vec!["main.esp".to_string()]);
+            assert_eq!(std::fs::read_to_string(&vanilla).unwrap(), "c");
+            assert_eq!(
+                std::fs::read_to_string(backup.join("main.esp")).unwrap(),
+                "vanilla"
+            );
+
+            let res = deployer.undeploy(conn).unwrap();
+            assert_eq!(res.restored, vec!["main.esp".to_string()]);
+            assert_eq!(std::fs::read_to_string(&vanilla).unwrap(), "vanilla");
+            assert!(!backup.join("main.esp").exists());
+            assert!(!target.join("data").exists());
+        }
+
+        // 收集部署后写入目标目录的新文件
+        #[test]
+        fn test_capture() {
+            let mut fndb = FNDB::new(":memory:");
+            fndb.connect_rw().unwrap();
+            fndb.init_view().unwrap();
+            let conn = fndb.try_conn().unwrap();
+
+            let files = ["main.esp"];
+            let ids = insert_memory_packs(conn, &files);
+            // 手动设置的优先级远超计数器
+            ListPack::SetPriority(&ids[0], 1e9).execute(conn).unwrap();
+
+            let root = std::env::temp_dir().join("vfslink_deploy_capture");
+            let _ = std::fs::remove_dir_all(&root);
+            let target = root.join("target");
+            std::fs::create_dir_all(&target).unwrap();
+            std::fs::write(target.join("vanilla.txt"), "vanilla").unwrap();
+            let deployer = memory_deployer(&target, &ids, &files);
+            let overwrite = root.join("overwrite");
+            let info = |name: &str| InfoBase {
+                id: Uuid::new_v4(),
+                name: name.to_string(),
+                tag: vec![],
+                description: None,
+                author: None,
+                version: None,
+            };
+
+            // 没有部署会话时无法区分原有文件
+            assert!(matches!(
+                deployer.capture(conn, overwrite.to_str().unwrap(), info("overwrite")),
+                Err(Error::NotFound(_))
+            ));
+            assert!(target.join("vanilla.txt").is_file());
+            deployer.deploy(conn).unwrap();
+
+            // 模拟程序运行时写入的存档
+            std::fs::create_dir_all(target.join("saves")).unwrap();
+            std::fs::write(target.join("saves/slot1.sav"), "save").unwrap();
+
+            // 注册失败时文件放回目标目录
+            let memory = root.join("memory");
+            ListPack::InsertSource(
+                Arc::new(MemorySource::new(memory.to_str().unwrap())),
+                info("memory"),
+            )
+            .execute(conn)
+            .unwrap();
+            assert!(matches!(
+                deployer.capture(conn, memory.to_str().unwrap(), info("overwrite")),
+                Err(Error::InvalidInput(_))
+            ));
+            assert_eq!(
+                std::fs::read_to_string(target.join("saves/slot1.sav")).unwrap(),
+                "save"
+            );
+            assert!(!memory.join("saves/slot1.sav").exists());
+
+            let res = deployer
+                .capture(conn, overwrite.to_str().unwrap(), info("overwrite"))
+                .unwrap();
+            assert_eq!(res.files, vec!["saves/slot1.sav".to_string()]);
+            assert!(!target.join("saves/slot1.sav").exists());
+            assert_eq!(
+                std::fs::read_to_string(overwrite.join("saves/slot1.sav")).unwrap(),
+                "save"
+            );
+
+            // 收集包在当前方案中优先级最高且已启用
+            let packs = ViewPack::GetPackInfo.execute(conn).unwrap().as_pack_info();
+            let top = packs.last().unwrap();
+            assert_eq!(top.id, res.pack_id);
+            assert!(top.is_active && top.priority > 1_000_000_000);
+            assert_eq!(
+                std::fs::read_to_string(target.join("vanilla.txt")).unwrap(),
+                "vanilla"
+            );
+
+            // 之后插入的包不会与收集包的优先级冲突
+            ListPack::InsertSource(Arc::new(memory_source("d", &files)), info("d"))
+                .execute(conn)
+                .unwrap();
 
-        //TODO：移除所有部署文件
+            // 重新部署后存档由收集包以符号链接提供
+            let res = deployer.redeploy(conn).unwrap();
+            assert_eq!(res.added, vec!["saves/slot1.sav".to_string()]);
+            assert_eq!(
+                std::fs::read_link(target.join("saves/slot1.sav")).unwrap(),
+                overwrite.join("saves/slot1.sav")
+            );
+            deployer.undeploy(conn).unwrap();
+            assert!(matches!(
+                deployer.capture(conn, overwrite.to_str().unwrap(), info("overwrite")),
+                Err(Error::NotFound(_))
+            ));
+        }
 
-        //TODO：重新部署所有文件
+        // 切换包状态后增量重新部署
+        #[test]
+        fn test_redeploy() {
+            let mut fndb = FNDB::new(":memory:");
+            fndb.connect_rw().unwrap();
+            fndb.init_view().unwrap();
+            let conn = fndb.try_conn().unwrap();
+
+            let files = ["main.esp", "data/a.txt"];
+            let ids = insert_memory_packs(conn, &files);
+            let target = std::env::temp_dir().join("vfslink_redeploy");
+            let _ = std::fs::remove_dir_all(&target);
+            let deployer = memory_deployer(&target, &ids, &files);
+            deployer.deploy(conn).unwrap();
+
+            let sorted = |mut v: Vec<String>| {
+                v.sort();
+                v
+            };
+            let all = vec!["data/a.txt".to_string(), "main.esp".to_string()];
+            let contents = || {
+                files
+                    .iter()
+                    .map(|p| std::fs::read_to_string(target.join(p)).unwrap())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(contents(), vec!["c", "c"]);
+
+            // 停用顶层包后由下一个包接管
+            ListPack::SetActive(&ids[2], false).execute(conn).unwrap();
+            let res = deployer.redeploy(conn).unwrap();
+            assert_eq!(sorted(res.replaced), all);
+            assert!(res.added.is_empty() && res.removed.is_empty() && res.unchanged.is_empty());
+            assert_eq!(contents(), vec!["b", "b"]);
+
+            // 调整优先级后由新的顶层包提供
+            ListPack::SetPriority(&ids[0], 10.0).execute(conn).unwrap();
+            let res = deployer.redeploy(conn).unwrap();
+            assert_eq!(sorted(res.replaced), all);
+            assert_eq!(contents(), vec!["a", "a"]);
+
+            // 没有启用的包时移除所有部署文件与新建的目录
+            ListPack::SetActive(&ids[0], false).execute(conn).unwrap();
+            ListPack::SetActive(&ids[1], false).execute(conn).unwrap();
+            let res = deployer.redeploy(conn).unwrap();
+            assert_eq!(sorted(res.removed), all);
+            assert_eq!(res.removed_dirs, vec!["data".to_string()]);
+            assert!(res.added.is_empty() && res.replaced.is_empty());
+            assert!(std::fs::read_dir(&target).unwrap().next().is_none());
+
+            // 重新启用后再次部署
+            ListPack::SetActive(&ids[1], true).execute(conn).unwrap();
+            let res = deployer.redeploy(conn).unwrap();
+            assert_eq!(sorted(res.added), all);
+            assert_eq!(res.created_dirs, vec!["data".to_string()]);
+            assert!(res.removed.is_empty() && res.replaced.is_empty());
+            assert_eq!(contents(), vec!["b", "b"]);
+
+            // 没有变化时不做任何操作
+            let res = deployer.redeploy(conn).unwrap();
+            assert_eq!(sorted(res.unchanged), all);
+            assert!(res.added.is_empty() && res.removed.is_empty() && res.replaced.is_empty());
+        }
     }
 }